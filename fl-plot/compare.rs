@@ -0,0 +1,314 @@
+// Two recordings, like before and after a kernel or config change, compared
+// tool by tool: their distributions (as CDFs), their percentiles, and whether
+// the candidate's values differ from the baseline's by more than chance.
+
+use crate::stats::Summary;
+
+const BASELINE_COLOR: &str = "#1f77b4";
+const CANDIDATE_COLOR: &str = "#ff7f0e";
+
+// Beyond which, a CDF is drawn from evenly spaced quantiles
+const MAX_CDF_POINTS: usize = 1000;
+
+fn values_by_tool(
+    file_path: &str,
+    format: crate::input::InputFormat,
+) -> Result<std::collections::BTreeMap<String, Vec<f64>>, Box<dyn std::error::Error>> {
+    let input = crate::input::read(Some(file_path))?;
+    let mut by_tool = std::collections::BTreeMap::<String, Vec<f64>>::new();
+    crate::input::events(&input, format, |event| {
+        // Which couldn't be ranked
+        if event.value.is_nan() {
+            return;
+        }
+        by_tool.entry(event.tool).or_default().push(event.value);
+    });
+    for values in by_tool.values_mut() {
+        values.sort_by(f64::total_cmp);
+    }
+    Ok(by_tool)
+}
+
+// Tools whose values are better when lower, where an increase is a regression
+fn lower_is_better(tool: &str) -> bool {
+    matches!(tool, "cpu_pct" | "mem_pct" | "runq_len" | "cpu_migrate") || tool.ends_with("_lat")
+}
+
+// The complementary error function, to within 1.2e-7 (from Numerical Recipes)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * poly.exp();
+    match x >= 0.0 {
+        true => r,
+        false => 2.0 - r,
+    }
+}
+
+// A Mann-Whitney U test, which makes no assumptions about the shape of the
+// distributions (latencies are rarely normal). Takes sorted values. The z
+// score is positive when the candidate's values tend to be higher, and the
+// p value is two-sided, from the normal approximation (with ties corrected).
+fn mann_whitney_u(baseline: &[f64], candidate: &[f64]) -> Option<(f64, f64)> {
+    let (n1, n2) = (baseline.len() as f64, candidate.len() as f64);
+    if baseline.is_empty() || candidate.is_empty() {
+        return None;
+    }
+    // Merged, and ranked from 1, with ties given the average of their ranks
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let (mut i, mut j) = (0, 0);
+    let mut rank = 1.0;
+    while i < baseline.len() || j < candidate.len() {
+        let value = match (baseline.get(i), candidate.get(j)) {
+            (Some(a), Some(b)) => a.min(*b),
+            (Some(a), None) => *a,
+            (None, Some(b)) => *b,
+            (None, None) => unreachable!(),
+        };
+        let (i0, j0) = (i, j);
+        while baseline.get(i) == Some(&value) {
+            i += 1;
+        }
+        while candidate.get(j) == Some(&value) {
+            j += 1;
+        }
+        let n = (i - i0 + j - j0) as f64;
+        let avg = rank + (n - 1.0) / 2.0;
+        rank_sum += avg * (i - i0) as f64;
+        ties += n * n * n - n;
+        rank += n;
+    }
+    let n = n1 + n2;
+    let u1 = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let u2 = n1 * n2 - u1;
+    let mean = n1 * n2 / 2.0;
+    let var = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if var <= 0.0 {
+        // Every value is the same
+        return Some((0.0, 1.0));
+    }
+    // With a continuity correction
+    let diff = u2 - mean;
+    let z = (diff.abs() - 0.5).max(0.0).copysign(diff) / var.sqrt();
+    let p = erfc(z.abs() / std::f64::consts::SQRT_2);
+    Some((z, p.min(1.0)))
+}
+
+// From sorted values, as (value, fraction of values at or below it)
+fn cdf(values: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let n = values.len();
+    let step = n.div_ceil(MAX_CDF_POINTS).max(1);
+    let mut points: Vec<usize> = (step - 1..n).step_by(step).collect();
+    if points.last() != Some(&(n - 1)) {
+        points.push(n - 1);
+    }
+    let xs = points.iter().map(|i| values[*i]).collect();
+    let ys = points.iter().map(|i| (*i + 1) as f64 / n as f64).collect();
+    (xs, ys)
+}
+
+#[derive(PartialEq, Eq)]
+enum Verdict {
+    Regression,
+    Improvement,
+    // Significantly different, for tools where neither is better
+    Higher,
+    Lower,
+    Same,
+}
+
+impl Verdict {
+    fn of(tool: &str, z: f64, p: f64, significance: f64) -> Self {
+        match (p < significance, z > 0.0, lower_is_better(tool)) {
+            (false, _, _) => Verdict::Same,
+            (true, true, true) => Verdict::Regression,
+            (true, false, true) => Verdict::Improvement,
+            (true, true, false) => Verdict::Higher,
+            (true, false, false) => Verdict::Lower,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Verdict::Regression => "regression",
+            Verdict::Improvement => "improvement",
+            Verdict::Higher => "higher",
+            Verdict::Lower => "lower",
+            Verdict::Same => "no significant change",
+        }
+    }
+}
+
+struct Comparison {
+    tool: String,
+    baseline: Summary,
+    candidate: Summary,
+    z: f64,
+    p: f64,
+    verdict: Verdict,
+}
+
+fn change(baseline: f64, candidate: f64) -> String {
+    match baseline == 0.0 {
+        true => "".to_string(),
+        false => format!(" ({:+.1}%)", (candidate - baseline) / baseline * 100.0),
+    }
+}
+
+fn table_html(comparisons: &[Comparison], significance: f64) -> String {
+    let mut html = String::from("<table id=\"summary\">\n<tr><th>tool</th><th>count</th>");
+    for (name, _) in comparisons
+        .first()
+        .map(|c| c.baseline.stats())
+        .unwrap_or_default()
+    {
+        html.push_str(&format!("<th>{name}</th>"));
+    }
+    html.push_str(&format!("<th>p (of {significance})</th><th></th></tr>\n"));
+    for c in comparisons {
+        let class = match c.verdict {
+            Verdict::Regression => " class=\"regression\"",
+            Verdict::Improvement => " class=\"improvement\"",
+            _ => "",
+        };
+        let tool = crate::flame::html_string(&c.tool);
+        html.push_str(&format!(
+            "<tr{class}><td>{tool}</td><td>{} / {}</td>",
+            c.baseline.count, c.candidate.count
+        ));
+        for ((_, a), (_, b)) in c.baseline.stats().into_iter().zip(c.candidate.stats()) {
+            html.push_str(&format!("<td>{a} / {b}{}</td>", change(a, b)));
+        }
+        html.push_str(&format!(
+            "<td>{:.2e}</td><td>{}</td></tr>\n",
+            c.p,
+            c.verdict.name()
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+// Over the statistics table's style
+const VERDICT_STYLE: &str = "<style>
+  #summary tr.regression { background: #fdd; font-weight: bold; }
+  #summary tr.improvement { background: #dfd; }
+</style>
+";
+
+// For each tool in both recordings, the CDFs of both, next to bars of their
+// percentiles, and a table of how the candidate compares to the baseline
+// below (baseline / candidate), with significant regressions highlighted.
+pub fn write_html(
+    baseline: &str,
+    candidate: &str,
+    format: crate::input::InputFormat,
+    significance: f64,
+    output_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use plotly::common::{Line, Marker, Mode};
+    use plotly::layout::{Axis, AxisType};
+    let baseline_by_tool = values_by_tool(baseline, format)?;
+    let mut candidate_by_tool = values_by_tool(candidate, format)?;
+    let mut both = Vec::new();
+    for (tool, baseline_values) in baseline_by_tool {
+        match candidate_by_tool.remove(&tool) {
+            Some(candidate_values) => both.push((tool, baseline_values, candidate_values)),
+            None => log::warn!("Only in the baseline: {tool}"),
+        }
+    }
+    for tool in candidate_by_tool.keys() {
+        log::warn!("Only in the candidate: {tool}");
+    }
+    if both.is_empty() {
+        return Err("No tools in both recordings to compare".into());
+    }
+    let mut p = plotly::Plot::new();
+    let mut subplots = crate::subplots::Subplots::new(both.len(), 2);
+    let mut comparisons = Vec::with_capacity(both.len());
+    for (i, (tool, baseline_values, candidate_values)) in both.into_iter().enumerate() {
+        let (Some(b), Some(c)) = (
+            Summary::from(baseline_values.clone()),
+            Summary::from(candidate_values.clone()),
+        ) else {
+            continue;
+        };
+        let (z, pv) = mann_whitney_u(&baseline_values, &candidate_values).unwrap_or((0.0, 1.0));
+        let verdict = Verdict::of(&tool, z, pv, significance);
+        let title = match verdict {
+            Verdict::Same => tool.clone(),
+            ref verdict => format!("{tool}: {} (p = {pv:.2e})", verdict.name()),
+        };
+        // Cdfs on the left, percentiles on the right
+        let (cdf_x, cdf_y) = crate::subplots::axis_ids(2 * i);
+        let (bar_x, bar_y) = crate::subplots::axis_ids(2 * i + 1);
+        let runs = [
+            ("baseline", BASELINE_COLOR, &baseline_values, &b),
+            ("candidate", CANDIDATE_COLOR, &candidate_values, &c),
+        ];
+        for (name, color, values, summary) in runs {
+            let (xs, ys) = cdf(values);
+            let t = plotly::Scatter::new(xs, ys)
+                .mode(Mode::Lines)
+                .line(Line::new().color(color))
+                .name(name)
+                .legend_group(name)
+                .show_legend(i == 0)
+                .x_axis(&cdf_x)
+                .y_axis(&cdf_y);
+            p.add_trace(t);
+            let (names, values): (Vec<&str>, Vec<f64>) = summary.stats().into_iter().unzip();
+            let t = plotly::Bar::new(names, values)
+                .marker(Marker::new().color(color))
+                .name(name)
+                .legend_group(name)
+                .show_legend(false)
+                .x_axis(&bar_x)
+                .y_axis(&bar_y);
+            p.add_trace(t);
+        }
+        let unit = crate::unit(&tool);
+        // Latencies span orders of magnitude
+        let value_axis = match tool.ends_with("_lat") {
+            true => Axis::new().type_(AxisType::Log).title(unit),
+            false => Axis::new().title(unit),
+        };
+        let cdf_axis = Axis::new().title("fraction of events");
+        subplots.add(2 * i, &title, value_axis, cdf_axis);
+        let bar_axis = Axis::new().title(unit);
+        subplots.add(2 * i + 1, "", Axis::new(), bar_axis);
+        comparisons.push(Comparison {
+            tool,
+            baseline: b,
+            candidate: c,
+            z,
+            p: pv,
+            verdict,
+        });
+    }
+    for c in comparisons
+        .iter()
+        .filter(|c| c.verdict == Verdict::Regression)
+    {
+        log::warn!("Regression in {} (z = {:.2}, p = {:.2e})", c.tool, c.z, c.p);
+    }
+    let table = format!(
+        "{}{VERDICT_STYLE}{}",
+        crate::stats::TABLE_STYLE,
+        table_html(&comparisons, significance)
+    );
+    let html = subplots
+        .html(p)
+        .replacen("</body>", &format!("{table}</body>"), 1);
+    std::fs::write(output_file, html)?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+// Flame graphs (and icicle charts) from folded stacks, like those from
+// `fl --profile -f folded`. Rendered as a standalone html file, which draws
+// the graph as an svg and handles search and zoom by itself.
+
+// Stacks are merged into a tree, where each frame counts the samples (or
+// whatever weight) of the stacks which pass through it
+#[derive(Default)]
+struct Node {
+    count: u64,
+    children: std::collections::BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn insert(&mut self, frames: &[&str], count: u64) {
+        self.count += count;
+        if let Some((frame, rest)) = frames.split_first() {
+            let child = self.children.entry(frame.to_string()).or_default();
+            child.insert(rest, count);
+        }
+    }
+}
+
+// Lines like "task;main;read;vfs_read_[k] 12"
+fn read_folded(input: &str) -> Node {
+    let mut root = Node::default();
+    for line in input.lines() {
+        let Some((stack, count)) = line.trim_end().rsplit_once(' ') else {
+            continue;
+        };
+        match count.parse::<f64>() {
+            Ok(count) if count >= 0.0 => {
+                let frames: Vec<&str> = stack.split(';').collect();
+                root.insert(&frames, count.round() as u64);
+            }
+            _ => log::error!("Error parsing folded stack: {line}"),
+        }
+    }
+    root
+}
+
+// Frames as [name, depth, start, width], where the start and width are in the
+// same units as the counts
+struct Frames {
+    names: Vec<String>,
+    ids: std::collections::HashMap<String, u64>,
+    frames: Vec<[u64; 4]>,
+}
+
+impl Frames {
+    fn from(root: &Node) -> Self {
+        let mut frames = Self {
+            names: vec!["all".to_string()],
+            ids: std::collections::HashMap::new(),
+            frames: vec![[0, 0, 0, root.count]],
+        };
+        frames.flatten(root, 1, 0);
+        frames
+    }
+
+    fn flatten(&mut self, node: &Node, depth: u64, mut start: u64) {
+        for (name, child) in &node.children {
+            let id = *self.ids.entry(name.clone()).or_insert_with(|| {
+                self.names.push(name.clone());
+                self.names.len() as u64 - 1
+            });
+            self.frames.push([id, depth, start, child.count]);
+            self.flatten(child, depth + 1, start);
+            start += child.count;
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            // Also keeps "</script>" from ending the script early
+            '<' => json.push_str("\\u003c"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+pub fn html_string(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn write_html(
+    input: &str,
+    title: &str,
+    output_file: &str,
+    icicle: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = read_folded(input);
+    if root.count == 0 {
+        return Err(format!("No folded stacks in {title}").into());
+    }
+    let Frames { names, frames, .. } = Frames::from(&root);
+    let names: Vec<String> = names.iter().map(|name| json_string(name)).collect();
+    let frames: Vec<String> = frames
+        .iter()
+        .map(|[id, depth, start, width]| format!("[{id},{depth},{start},{width}]"))
+        .collect();
+    let html = TEMPLATE
+        .replace("{{TITLE}}", &html_string(title))
+        .replace("{{ICICLE}}", if icicle { "true" } else { "false" })
+        .replace("{{NAMES}}", &format!("[{}]", names.join(",")))
+        .replace("{{FRAMES}}", &format!("[{}]", frames.join(",")));
+    std::fs::write(output_file, html)?;
+    Ok(())
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+  body { margin: 0; font-family: monospace; font-size: 12px; }
+  #bar { display: flex; gap: 8px; align-items: center; padding: 8px; }
+  #info { flex: 1; overflow: hidden; white-space: nowrap; text-overflow: ellipsis; }
+  #chart rect { stroke: white; stroke-width: 0.5; cursor: pointer; }
+  #chart rect.faded { opacity: 0.6; }
+  #chart rect.match { fill: rgb(230, 0, 230); }
+  #chart text { font-size: 11px; pointer-events: none; }
+</style>
+</head>
+<body>
+<div id="bar">
+  <button id="reset">Reset zoom</button>
+  <input id="search" size="32" placeholder="Search (regex)">
+  <span id="matched"></span>
+  <span id="info"></span>
+</div>
+<svg id="chart" width="100%"></svg>
+<script>
+const names = {{NAMES}};
+const frames = {{FRAMES}};
+const icicle = {{ICICLE}};
+const frameHeight = 17;
+const charWidth = 7;
+const svgns = "http://www.w3.org/2000/svg";
+const chart = document.getElementById("chart");
+const info = document.getElementById("info");
+const search = document.getElementById("search");
+const matched = document.getElementById("matched");
+const total = frames[0][3];
+const maxDepth = frames.reduce((max, f) => Math.max(max, f[1]), 0);
+let zoomed = 0;
+let pattern = null;
+
+chart.setAttribute("height", (maxDepth + 1) * frameHeight);
+
+// Warm colors, stable for each name, and more orange for kernel frames
+function color(name) {
+  let h = 0;
+  for (let i = 0; i < name.length; i++) h = (h * 31 + name.charCodeAt(i)) >>> 0;
+  const a = (h % 97) / 97, b = (h % 89) / 89;
+  if (name.endsWith("_[k]")) return `rgb(${200 + 55 * a | 0}, ${110 + 60 * b | 0}, 40)`;
+  return `rgb(${205 + 50 * a | 0}, ${230 * b | 0}, ${55 * a | 0})`;
+}
+
+function percent(width) {
+  return (100 * width / total).toFixed(2) + "%";
+}
+
+function label(name, width) {
+  const fits = Math.floor((width - 6) / charWidth);
+  if (fits < 3) return "";
+  return name.length <= fits ? name : name.slice(0, fits - 2) + "..";
+}
+
+// The share of all samples in matching frames, without counting nested matches twice
+function showMatched() {
+  if (!pattern) {
+    matched.textContent = "";
+    return;
+  }
+  let sum = 0, end = -1;
+  const spans = frames.filter(f => pattern.test(names[f[0]])).map(f => [f[2], f[2] + f[3]]);
+  spans.sort((a, b) => a[0] - b[0] || b[1] - a[1]);
+  for (const [start, stop] of spans) {
+    if (start >= end) {
+      sum += stop - start;
+      end = stop;
+    } else if (stop > end) {
+      sum += stop - end;
+      end = stop;
+    }
+  }
+  matched.textContent = "Matched: " + percent(sum);
+}
+
+function render() {
+  const width = chart.clientWidth;
+  const [, zoomDepth, zoomStart, zoomWidth] = frames[zoomed];
+  chart.textContent = "";
+  frames.forEach(([id, depth, start, w], i) => {
+    const inside = start >= zoomStart && start + w <= zoomStart + zoomWidth;
+    const around = depth < zoomDepth && start <= zoomStart && start + w >= zoomStart + zoomWidth;
+    if (!inside && !around) return;
+    const x = around ? 0 : (start - zoomStart) / zoomWidth * width;
+    const fw = around ? width : w / zoomWidth * width;
+    if (fw < 0.5) return;
+    const y = (icicle ? depth : maxDepth - depth) * frameHeight;
+    const name = names[id];
+    const g = document.createElementNS(svgns, "g");
+    const rect = document.createElementNS(svgns, "rect");
+    rect.setAttribute("x", x);
+    rect.setAttribute("y", y);
+    rect.setAttribute("width", fw);
+    rect.setAttribute("height", frameHeight - 1);
+    rect.setAttribute("fill", color(name));
+    if (around) rect.classList.add("faded");
+    if (pattern && pattern.test(name)) rect.classList.add("match");
+    const title = document.createElementNS(svgns, "title");
+    title.textContent = `${name} (${w}, ${percent(w)})`;
+    rect.appendChild(title);
+    const text = document.createElementNS(svgns, "text");
+    text.setAttribute("x", x + 3);
+    text.setAttribute("y", y + frameHeight - 5);
+    text.textContent = label(name, fw);
+    g.appendChild(rect);
+    g.appendChild(text);
+    g.addEventListener("click", () => { zoomed = i; render(); });
+    g.addEventListener("mouseover", () => { info.textContent = title.textContent; });
+    chart.appendChild(g);
+  });
+}
+
+document.getElementById("reset").addEventListener("click", () => { zoomed = 0; render(); });
+search.addEventListener("input", () => {
+  try {
+    pattern = search.value ? new RegExp(search.value) : null;
+  } catch (e) {
+    return;
+  }
+  showMatched();
+  render();
+});
+window.addEventListener("resize", render);
+render();
+</script>
+</body>
+</html>
+"##;
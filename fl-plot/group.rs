@@ -0,0 +1,52 @@
+// Each tool's events, grouped (again) by tool, or by task or pid
+
+use crate::Series;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    Tool,
+    Task,
+    Pid,
+}
+
+// Beyond which, a tool's smallest groups are lumped together
+const MAX_GROUPS: usize = 8;
+
+// In order of the tools' names, then of the groups' sizes
+pub fn series(
+    input: &str,
+    format: crate::input::InputFormat,
+    group_by: GroupBy,
+) -> Vec<Series<String>> {
+    use std::collections::HashMap;
+    let mut by_tool = HashMap::<String, HashMap<String, Vec<(String, f64)>>>::new();
+    crate::input::events(input, format, |event| {
+        let group = match group_by {
+            GroupBy::Tool => event.tool.clone(),
+            GroupBy::Task => event.task,
+            GroupBy::Pid => event.pid.to_string(),
+        };
+        let entry = by_tool.entry(event.tool).or_default().entry(group);
+        entry.or_default().push((event.time, event.value));
+    });
+    let mut by_tool: Vec<_> = by_tool.into_iter().collect();
+    by_tool.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut series = Vec::new();
+    for (tool, groups) in by_tool {
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by(|(a, a_values), (b, b_values)| {
+            b_values.len().cmp(&a_values.len()).then(a.cmp(b))
+        });
+        if groups.len() > MAX_GROUPS {
+            let others = groups.split_off(MAX_GROUPS - 1);
+            let others = others.into_iter().flat_map(|(_, values)| values).collect();
+            groups.push(("others".to_string(), others));
+        }
+        series.extend(groups.into_iter().map(|(group, values)| Series {
+            tool: tool.clone(),
+            group,
+            values,
+        }));
+    }
+    series
+}
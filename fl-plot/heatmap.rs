@@ -0,0 +1,92 @@
+// Latency heatmaps, one per tool (or group of a tool's events), of how many
+// events fell into each time window and (log2) range of values. Bimodal
+// latencies, and how they shift over time, stand out here where a scatter of
+// every event would be a smear.
+
+use crate::Series;
+
+// As the slots of `fl --func-hist`: [0, 2), [2, 4), [4, 8), ...
+fn slot(value: f64) -> usize {
+    match value < 2.0 {
+        true => 0,
+        false => (value.log2().floor() as usize).min(62),
+    }
+}
+
+fn slot_range(slot: usize) -> String {
+    let lo = if slot == 0 { 0 } else { 1u64 << slot };
+    format!("{lo}-{}", 1u64 << (slot + 1))
+}
+
+struct Heatmap {
+    // Where each window starts
+    windows: Vec<f64>,
+    slots: Vec<String>,
+    // By slot, then window. None (a gap in the plot) where nothing happened.
+    counts: Vec<Vec<Option<u64>>>,
+}
+
+impl Heatmap {
+    // From (time, value) pairs, in windows from `first`, so that the windows
+    // of every tool line up
+    fn from(values: &[(f64, f64)], first: u64, window: f64) -> Self {
+        let window_of = |t: f64| (t / window).floor() as u64 - first;
+        let n_windows = values.iter().map(|(t, _)| window_of(*t)).max().unwrap_or(0) + 1;
+        let lo = values.iter().map(|(_, v)| slot(*v)).min().unwrap_or(0);
+        let hi = values.iter().map(|(_, v)| slot(*v)).max().unwrap_or(0);
+        let mut counts = vec![vec![None; n_windows as usize]; hi - lo + 1];
+        for (t, v) in values {
+            let count = &mut counts[slot(*v) - lo][window_of(*t) as usize];
+            *count = Some(count.unwrap_or(0) + 1);
+        }
+        Self {
+            windows: (first..first + n_windows)
+                .map(|i| i as f64 * window)
+                .collect(),
+            slots: (lo..=hi).map(slot_range).collect(),
+            counts,
+        }
+    }
+}
+
+pub fn write_html(
+    series: Vec<Series<String>>,
+    window_ms: u64,
+    output_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(timed) = crate::time::Timed::from(series) else {
+        return Err("No events to plot".into());
+    };
+    let window = window_ms.max(1) as f64 / 1000.0;
+    let first = timed
+        .series
+        .iter()
+        .map(|series| (series.values[0].0 / window).floor() as u64)
+        .min()
+        .unwrap_or(0);
+    let mut p = plotly::Plot::new();
+    let mut subplots = crate::subplots::Subplots::new(timed.series.len(), 1);
+    for (i, series) in timed.series.iter().enumerate() {
+        let Heatmap {
+            windows,
+            slots,
+            counts,
+        } = Heatmap::from(&series.values, first, window);
+        let title = series.title();
+        let (x_id, y_id) = crate::subplots::axis_ids(i);
+        let windows = windows.into_iter().map(|t| timed.kind.x(t)).collect();
+        let t = plotly::HeatMap::new(windows, slots, counts)
+            .name(&title)
+            .x_axis(x_id)
+            .y_axis(y_id)
+            .show_scale(false)
+            .hover_template(format!(
+                "{title}<br>%{{x}}: %{{z}} in %{{y}}<extra></extra>"
+            ));
+        p.add_trace(t);
+        let y = plotly::layout::Axis::new().title(crate::unit(&series.tool));
+        subplots.add(i, &title, timed.kind.axis(), y);
+    }
+    std::fs::write(output_file, subplots.html(p))?;
+    Ok(())
+}
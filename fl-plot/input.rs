@@ -0,0 +1,146 @@
+// Events as `fl` writes them, in any of its (non-folded) output formats.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// Guess from the first line
+    Auto,
+    Columnar,
+    Csv,
+    Json,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Event {
+    pub tool: String,
+    #[serde(deserialize_with = "time")]
+    pub time: String,
+    pub task: String,
+    pub pid: i32,
+    pub value: f64,
+}
+
+// Times are numbers in JSON (for the microsecond formats), and strings otherwise
+fn time<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Time {
+        Usecs(u64),
+        Formatted(String),
+    }
+    match serde::Deserialize::deserialize(deserializer)? {
+        Time::Usecs(us) => Ok(us.to_string()),
+        Time::Formatted(time) => Ok(time),
+    }
+}
+
+// Reads the whole file, or standard input if there's no file (or it's "-")
+pub fn read(file_path: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    use std::io::Read;
+    match file_path {
+        Some(file_path) if file_path != "-" => Ok(std::fs::read_to_string(file_path)?),
+        _ => {
+            // Piped from `fl`, which is stopped with a SIGINT, and that would
+            // stop us too, before it's done writing
+            unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) };
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+fn detect(input: &str) -> InputFormat {
+    let Some(line) = input.lines().find(|line| !line.trim().is_empty()) else {
+        return InputFormat::Csv;
+    };
+    match line {
+        line if line.starts_with('{') => InputFormat::Json,
+        line if line.starts_with("tool,") => InputFormat::Csv,
+        line if line.split(',').count() >= 5 => InputFormat::Csv,
+        _ => InputFormat::Columnar,
+    }
+}
+
+// The first whitespace-separated token, and what's after it
+fn token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(end) => Some((&s[..end], &s[end..])),
+        None if !s.is_empty() => Some((s, "")),
+        None => None,
+    }
+}
+
+// Lines like "rq_lat       00:00:01      kworker/0:1          42       17"
+fn columnar_event(line: &str) -> Option<Event> {
+    let (tool, rest) = token(line)?;
+    let (time, rest) = token(rest)?;
+    // Tasks can have spaces, but are padded to 20 characters (unless longer,
+    // and so more likely to be a function's name than a task's)
+    let rest = rest.trim_start();
+    let (task, rest) = match (rest.get(..20), rest.get(20..)) {
+        (Some(task), Some(rest)) if rest.starts_with(' ') => (task.trim_end(), rest),
+        _ => token(rest)?,
+    };
+    let (pid, rest) = token(rest)?;
+    let (value, _) = token(rest)?;
+    Some(Event {
+        tool: tool.to_string(),
+        time: time.to_string(),
+        task: task.to_string(),
+        pid: pid.parse().ok()?,
+        value: value.parse().ok()?,
+    })
+}
+
+fn columnar_events(input: &str, mut f: impl FnMut(Event)) {
+    for line in input.lines() {
+        // Skip the frames of stacks, which are indented, and the header
+        if line.trim().is_empty() || line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if token(line).is_some_and(|(tool, _)| tool == "tool") {
+            continue;
+        }
+        match columnar_event(line) {
+            Some(event) => f(event),
+            None => log::error!("Error parsing columnar event: {line}"),
+        }
+    }
+}
+
+fn csv_events(input: &str, mut f: impl FnMut(Event)) {
+    let has_headers = input.trim_start().starts_with("tool,");
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(has_headers)
+        .from_reader(input.as_bytes());
+    for r in rdr.deserialize::<Event>() {
+        match r {
+            Ok(event) => f(event),
+            Err(e) => log::error!("Error parsing CSV: {e}"),
+        }
+    }
+}
+
+// One object per line, with other fields (the detail and stacks) ignored
+fn json_events(input: &str, mut f: impl FnMut(Event)) {
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Event>(line) {
+            Ok(event) => f(event),
+            Err(e) => log::error!("Error parsing JSON: {e} in {line}"),
+        }
+    }
+}
+
+pub fn events(input: &str, format: InputFormat, f: impl FnMut(Event)) {
+    let format = match format {
+        InputFormat::Auto => detect(input),
+        format => format,
+    };
+    log::debug!("Input format: {format:?}");
+    match format {
+        InputFormat::Auto | InputFormat::Csv => csv_events(input, f),
+        InputFormat::Columnar => columnar_events(input, f),
+        InputFormat::Json => json_events(input, f),
+    }
+}
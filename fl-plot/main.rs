@@ -1,64 +1,106 @@
 use clap::Parser;
-use std::collections::HashMap;
+mod compare;
+mod flame;
+mod group;
+mod heatmap;
+mod input;
+mod stats;
+mod subplots;
+mod time;
 
 #[derive(Debug, Parser)]
 #[clap(version)]
 struct Cli {
-    /// Path to a csv input file (with a header) for plotting
-    #[arg(short, long)]
-    input_file: String,
+    /// Path to an input file for plotting, or standard input if not given (or '-')
+    ///
+    /// In any of the output formats of `fl`, except for folded stacks.
+    /// Or, for flame graphs, a file of folded stacks (from `fl -f folded`).
+    #[arg(short, long, verbatim_doc_comment)]
+    input_file: Option<String>,
+    /// The format of the input, guessed from its first line by default
+    #[arg(long, short = 'f', default_value = "auto")]
+    input_format: input::InputFormat,
     /// Path to an (html) output file for the plot
     #[arg(short, long, default_value = "fl.html")]
     output_file: String,
+    /// Render a flame graph of folded stacks, with search and zoom
+    #[arg(long, conflicts_with = "icicle")]
+    flame_graph: bool,
+    /// Like '--flame-graph', but upside-down, with the root frame at the top
+    #[arg(long)]
+    icicle: bool,
+    /// Plot a heatmap for each tool, of how many events fell into each time
+    /// window and log2 range of values
+    #[arg(long, conflicts_with_all = ["flame_graph", "icicle"])]
+    heatmap: bool,
+    /// The width of the time windows which percentiles (or heatmap cells) are taken over
+    #[arg(long, default_value = "1000")]
+    window_ms: u64,
+    /// Plot a series for each task or pid, instead of one for each tool
+    ///
+    /// Each tool is still plotted on its own, with its busiest groups
+    /// (and the rest lumped together).
+    #[arg(long, default_value = "tool", verbatim_doc_comment)]
+    group_by: group::GroupBy,
+    /// Compare a recording (the candidate) to this one, tool by tool
+    ///
+    /// Plots the distributions and percentiles of both, and tests whether
+    /// the candidate's values are significantly higher or lower.
+    #[arg(long, requires = "candidate", conflicts_with_all = ["flame_graph", "icicle", "heatmap"], verbatim_doc_comment)]
+    baseline: Option<String>,
+    /// See '--baseline'
+    #[arg(long, requires = "baseline")]
+    candidate: Option<String>,
+    /// The p value below which differences between recordings are significant
+    #[arg(long, default_value = "0.01")]
+    significance: f64,
 }
 
-#[allow(dead_code)]
-#[derive(serde::Deserialize)]
-struct Event {
-    tool: String,
-    time: String,
-    task: String,
-    pid: i32,
-    value: f64,
+// The (time, value) pairs of a tool's events, in one group
+pub struct Series<Time> {
+    pub tool: String,
+    pub group: String,
+    pub values: Vec<(Time, f64)>,
 }
 
-type PlottableByTool = HashMap<String, Vec<(String, f64)>>;
-
-fn many_plottable_from_csv_file_by_tool(
-    file_path: &str,
-) -> Result<PlottableByTool, Box<dyn std::error::Error>> {
-    let mut rdr = csv::ReaderBuilder::new().from_reader(std::fs::File::open(file_path)?);
-    let mut evs = PlottableByTool::new();
-    for r in rdr.deserialize::<Event>() {
-        match r {
-            Ok(event) => {
-                let entry = evs.entry(event.tool.clone()).or_default();
-                entry.push((event.time, event.value));
-            }
-            Err(e) => log::error!("Error parsing CSV: {e} in {file_path}"),
+impl<Time> Series<Time> {
+    pub fn title(&self) -> String {
+        match self.tool == self.group {
+            true => self.tool.clone(),
+            false => format!("{}: {}", self.tool, self.group),
         }
     }
-    Ok(evs)
 }
 
-fn plot_from_csv_file(file_path: &str) -> Result<plotly::Plot, Box<dyn std::error::Error>> {
-    let mut p = plotly::Plot::new();
-    p.set_layout(plotly::layout::Layout::new());
-    for (tool, values) in many_plottable_from_csv_file_by_tool(file_path)? {
-        let t = plotly::Scatter::new(
-            values.iter().map(|(x, _)| x.clone()).collect(),
-            values.iter().map(|(_, y)| *y).collect(),
-        )
-        .mode(plotly::common::Mode::LinesMarkers)
-        .name(tool);
-        p.add_trace(t);
+// What the values of each tool's events are
+fn unit(tool: &str) -> &'static str {
+    match tool {
+        "cpu_pct" | "mem_pct" => "percent",
+        "runq_len" => "tasks waiting",
+        "cpu_migrate" => "migrations",
+        "bio_stat" => "iops",
+        "profile" => "samples",
+        "func_lat_hist" => "calls",
+        tool if tool.ends_with("_lat") => "latency (us)",
+        _ => "value",
     }
-    Ok(p)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
     let opts = Cli::parse();
-    plot_from_csv_file(&opts.input_file)?.write_html(&opts.output_file);
-    Ok(())
+    if let (Some(baseline), Some(candidate)) = (&opts.baseline, &opts.candidate) {
+        let (format, significance) = (opts.input_format, opts.significance);
+        return compare::write_html(baseline, candidate, format, significance, &opts.output_file);
+    }
+    let input = input::read(opts.input_file.as_deref())?;
+    if opts.flame_graph || opts.icicle {
+        let title = opts.input_file.as_deref().unwrap_or("stdin");
+        return flame::write_html(&input, title, &opts.output_file, opts.icicle);
+    }
+    let series = group::series(&input, opts.input_format, opts.group_by);
+    if opts.heatmap {
+        return heatmap::write_html(series, opts.window_ms, &opts.output_file);
+    }
+    stats::write_html(series, opts.window_ms, &opts.output_file)
 }
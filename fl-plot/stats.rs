@@ -0,0 +1,151 @@
+// Percentiles of each tool's values, over time and overall. The tail, which
+// a scatter of every event hides under its bulk, gets its own traces.
+
+use crate::Series;
+
+const PERCENTILES: [(&str, f64); 4] =
+    [("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("p99.9", 99.9)];
+
+pub struct Summary {
+    pub count: usize,
+    min: f64,
+    // As in PERCENTILES
+    percentiles: [f64; 4],
+    max: f64,
+}
+
+impl Summary {
+    // Nearest-rank percentiles, so each is a value which was seen
+    pub fn from(mut values: Vec<f64>) -> Option<Self> {
+        values.sort_by(f64::total_cmp);
+        let rank = |p: f64| ((p / 100.0 * values.len() as f64).ceil() as usize).max(1) - 1;
+        Some(Self {
+            count: values.len(),
+            min: *values.first()?,
+            percentiles: PERCENTILES.map(|(_, p)| values[rank(p)]),
+            max: *values.last()?,
+        })
+    }
+
+    // Named as in the traces and the table
+    pub fn stats(&self) -> Vec<(&'static str, f64)> {
+        let mut stats = vec![("min", self.min)];
+        stats.extend(
+            PERCENTILES
+                .iter()
+                .map(|(name, _)| *name)
+                .zip(self.percentiles),
+        );
+        stats.push(("max", self.max));
+        stats
+    }
+}
+
+// The starts of the (non-empty) windows, and a summary of each
+fn windowed(values: &[(f64, f64)], window: f64) -> Vec<(f64, Summary)> {
+    let mut windows = std::collections::BTreeMap::<u64, Vec<f64>>::new();
+    for (t, v) in values {
+        windows
+            .entry((t / window).floor() as u64)
+            .or_default()
+            .push(*v);
+    }
+    windows
+        .into_iter()
+        .filter_map(|(i, values)| Some((i as f64 * window, Summary::from(values)?)))
+        .collect()
+}
+
+fn table_html(summaries: &[(String, Summary)]) -> String {
+    let mut html = String::from("<table id=\"summary\">\n<tr><th>tool</th><th>count</th>");
+    for (name, _) in summaries
+        .first()
+        .map(|(_, s)| s.stats())
+        .unwrap_or_default()
+    {
+        html.push_str(&format!("<th>{name}</th>"));
+    }
+    html.push_str("</tr>\n");
+    for (tool, summary) in summaries {
+        let tool = crate::flame::html_string(tool);
+        html.push_str(&format!("<tr><td>{tool}</td><td>{}</td>", summary.count));
+        for (_, value) in summary.stats() {
+            html.push_str(&format!("<td>{value}</td>"));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+pub(crate) const TABLE_STYLE: &str = "<style>
+  #summary { margin: 16px; border-collapse: collapse; font-family: monospace; }
+  #summary th, #summary td { padding: 4px 12px; border-bottom: 1px solid #ddd; text-align: right; }
+  #summary th:first-child, #summary td:first-child { text-align: left; }
+</style>
+";
+
+// A subplot of each tool's statistics in every window, as lines, for each of
+// its groups, then a subplot of their counts, and a table of each group's
+// statistics overall below.
+pub fn write_html(
+    series: Vec<Series<String>>,
+    window_ms: u64,
+    output_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use plotly::common::{DashType, Line, Mode};
+    use plotly::layout::Axis;
+    let Some(timed) = crate::time::Timed::from(series) else {
+        return Err("No events to plot".into());
+    };
+    let window = window_ms.max(1) as f64 / 1000.0;
+    let by_tool: Vec<&[Series<f64>]> = timed.series.chunk_by(|a, b| a.tool == b.tool).collect();
+    let mut p = plotly::Plot::new();
+    let mut subplots = crate::subplots::Subplots::new(by_tool.len() + 1, 1);
+    let mut counts = Vec::with_capacity(timed.series.len());
+    let mut summaries = Vec::with_capacity(timed.series.len());
+    for (i, tool) in by_tool.iter().enumerate() {
+        let (x_id, y_id) = crate::subplots::axis_ids(i);
+        for series in tool.iter() {
+            let windows = windowed(&series.values, window);
+            let times: Vec<String> = windows.iter().map(|(t, _)| timed.kind.x(*t)).collect();
+            let stats: Vec<Vec<(&str, f64)>> = windows.iter().map(|(_, s)| s.stats()).collect();
+            for (j, (name, _)) in stats[0].iter().enumerate() {
+                let t = plotly::Scatter::new(times.clone(), stats.iter().map(|s| s[j].1).collect())
+                    .mode(Mode::Lines)
+                    .name(format!("{} {name}", series.group))
+                    .legend_group(series.title())
+                    .x_axis(&x_id)
+                    .y_axis(&y_id);
+                p.add_trace(t);
+            }
+            let values = windows.iter().map(|(_, s)| s.count).collect();
+            counts.push((series.title(), times, values));
+            let summary = Summary::from(series.values.iter().map(|(_, v)| *v).collect());
+            summaries.extend(summary.map(|summary| (series.title(), summary)));
+        }
+        let y = Axis::new().title(crate::unit(&tool[0].tool));
+        subplots.add(i, &tool[0].tool, timed.kind.axis(), y);
+    }
+    // Often in the thousands, where the values might be in the tens, and so
+    // on their own
+    let (x_id, y_id) = crate::subplots::axis_ids(by_tool.len());
+    for (title, times, values) in counts {
+        let t = plotly::Scatter::new(times, values)
+            .mode(Mode::Lines)
+            .line(Line::new().dash(DashType::Dot))
+            .name(format!("{title} count"))
+            .legend_group(title)
+            .x_axis(&x_id)
+            .y_axis(&y_id);
+        p.add_trace(t);
+    }
+    let y = Axis::new().title("events");
+    subplots.add(by_tool.len(), "count", timed.kind.axis(), y);
+    let table = format!("{TABLE_STYLE}{}", table_html(&summaries));
+    let html = subplots
+        .html(p)
+        .replacen("</body>", &format!("{table}</body>"), 1);
+    std::fs::write(output_file, html)?;
+    Ok(())
+}
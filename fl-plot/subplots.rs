@@ -0,0 +1,111 @@
+// Subplots in rows (and columns), each with its own axes and a title above it
+
+use plotly::layout::{Annotation, Axis, GridPattern, Layout, LayoutGrid};
+
+fn layout(rows: usize, columns: usize) -> Layout {
+    Layout::new()
+        .grid(
+            LayoutGrid::new()
+                .rows(rows)
+                .columns(columns)
+                .pattern(GridPattern::Independent),
+        )
+        .height(300 * rows)
+}
+
+// The first subplot's axes are "x" and "y", then "x2" and "y2", and so on,
+// across each row
+pub fn axis_ids(subplot: usize) -> (String, String) {
+    let n = match subplot {
+        0 => String::new(),
+        i => (i + 1).to_string(),
+    };
+    (format!("x{n}"), format!("y{n}"))
+}
+
+// Plotly's layout has axes for up to 8 subplots, the rest are set by
+// `Subplots::html()`
+fn add(mut layout: Layout, subplot: usize, title: &str, x: Axis, y: Axis) -> Layout {
+    let (x_id, y_id) = axis_ids(subplot);
+    layout.add_annotation(
+        Annotation::new()
+            .text(title)
+            .x_ref(format!("{x_id} domain"))
+            .y_ref(format!("{y_id} domain"))
+            .x(0.5)
+            .y(1.0)
+            .y_anchor(plotly::common::Anchor::Bottom)
+            .show_arrow(false),
+    );
+    match subplot {
+        0 => layout.x_axis(x).y_axis(y),
+        1 => layout.x_axis2(x).y_axis2(y),
+        2 => layout.x_axis3(x).y_axis3(y),
+        3 => layout.x_axis4(x).y_axis4(y),
+        4 => layout.x_axis5(x).y_axis5(y),
+        5 => layout.x_axis6(x).y_axis6(y),
+        6 => layout.x_axis7(x).y_axis7(y),
+        7 => layout.x_axis8(x).y_axis8(y),
+        _ => layout,
+    }
+}
+
+// Where the plot is drawn, in the page of `Plot::to_html()`, after which the
+// axes the layout has no room for are set
+const NEW_PLOT: &str = "await Plotly.newPlot(graph_div, ";
+
+// With the axes of any number of subplots, those past the 8th set by their
+// attributes once the plot is drawn
+pub struct Subplots {
+    layout: Layout,
+    // Like "xaxis9.title"
+    axes: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Subplots {
+    pub fn new(rows: usize, columns: usize) -> Self {
+        Self {
+            layout: layout(rows, columns),
+            axes: serde_json::Map::new(),
+        }
+    }
+
+    pub fn add(&mut self, subplot: usize, title: &str, x: Axis, y: Axis) {
+        let layout = std::mem::take(&mut self.layout);
+        if subplot >= 8 {
+            let (x_id, y_id) = axis_ids(subplot);
+            for (id, axis) in [(x_id, &x), (y_id, &y)] {
+                let Ok(serde_json::Value::Object(attrs)) = serde_json::to_value(axis) else {
+                    continue;
+                };
+                let (letter, n) = id.split_at(1);
+                for (attr, value) in attrs {
+                    self.axes.insert(format!("{letter}axis{n}.{attr}"), value);
+                }
+            }
+        }
+        self.layout = add(layout, subplot, title, x, y);
+    }
+
+    // The plot, as a page
+    pub fn html(self, mut plot: plotly::Plot) -> String {
+        plot.set_layout(self.layout);
+        let mut html = plot.to_html();
+        if self.axes.is_empty() {
+            return html;
+        }
+        let Some(at) = html
+            .find(NEW_PLOT)
+            .and_then(|i| html[i..].find("</script>").map(|j| i + j))
+        else {
+            log::warn!("Only the first 8 subplots' axes are titled");
+            return html;
+        };
+        // So that nothing in them ends the script
+        let axes = serde_json::Value::Object(self.axes)
+            .to_string()
+            .replace('<', "\\u003c");
+        html.insert_str(at, &format!("await Plotly.relayout(graph_div, {axes});\n"));
+        html
+    }
+}
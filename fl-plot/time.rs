@@ -0,0 +1,86 @@
+// The `time` column, as written by any of `fl`'s time formats
+
+// The time formats are either durations since `fl` started, or dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // Microseconds, hh:mm:ss or hh:mm:ss.mss
+    Duration,
+    // Utc, iso 8601
+    Date,
+}
+
+// In seconds, since `fl` started for durations, or since the epoch for dates
+pub fn parse(time: &str) -> Option<(Kind, f64)> {
+    if let Ok(us) = time.parse::<u64>() {
+        return Some((Kind::Duration, us as f64 / 1e6));
+    }
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(time) {
+        return Some((Kind::Date, t.timestamp_micros() as f64 / 1e6));
+    }
+    let mut parts = time.splitn(3, ':');
+    let hh = parts.next()?.parse::<u64>().ok()?;
+    let mm = parts.next()?.parse::<u64>().ok()?;
+    let ss = parts.next()?.parse::<f64>().ok()?;
+    Some((Kind::Duration, (hh * 3600 + mm * 60) as f64 + ss))
+}
+
+impl Kind {
+    // A time (from `parse`) as plotly takes it on an axis of this kind
+    pub fn x(self, secs: f64) -> String {
+        match self {
+            Kind::Duration => format!("{secs}"),
+            Kind::Date => chrono::DateTime::from_timestamp_micros((secs * 1e6).round() as i64)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn axis(self) -> plotly::layout::Axis {
+        use plotly::layout::{Axis, AxisType};
+        match self {
+            Kind::Duration => Axis::new().type_(AxisType::Linear).title("seconds"),
+            Kind::Date => Axis::new().type_(AxisType::Date).title("time (utc)"),
+        }
+    }
+}
+
+// Each series' (time, value) pairs, in order of time, where the times are
+// all of one kind, that of the first
+pub struct Timed {
+    pub kind: Kind,
+    pub series: Vec<crate::Series<f64>>,
+}
+
+impl Timed {
+    // None if there's nothing to plot
+    pub fn from(series: Vec<crate::Series<String>>) -> Option<Self> {
+        let mut kind = None;
+        let series = series
+            .into_iter()
+            .map(|series| {
+                let mut values: Vec<(f64, f64)> = series
+                    .values
+                    .iter()
+                    .filter_map(|(time, value)| match parse(time) {
+                        Some((k, t)) if *kind.get_or_insert(k) == k => Some((t, *value)),
+                        _ => {
+                            log::error!("Error parsing time: {time}");
+                            None
+                        }
+                    })
+                    .collect();
+                values.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+                crate::Series {
+                    tool: series.tool,
+                    group: series.group,
+                    values,
+                }
+            })
+            .filter(|series| !series.values.is_empty())
+            .collect();
+        Some(Self {
+            kind: kind?,
+            series,
+        })
+    }
+}
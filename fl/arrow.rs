@@ -0,0 +1,314 @@
+// Events in Arrow IPC or Parquet files, one for each tool (as each tool's events
+// have their own columns), for loading into data frames. The columns are the
+// fields of the json output, but typed, with times as timestamps and stacks as
+// lists of frames.
+
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+// Rows in a record batch, or in a parquet row group. Fewer when unbuffered,
+// where a batch is written every reporting interval.
+const BATCH_ROWS: usize = 1 << 16;
+
+#[derive(serde::Serialize)]
+struct Frame<'a> {
+    addr: u64,
+    symbol: Option<&'a str>,
+    offset: u64,
+    object: Option<&'a str>,
+}
+
+#[derive(serde::Serialize)]
+struct Row<'a, Fields> {
+    // Microseconds since the epoch
+    time: i64,
+    task: &'a str,
+    pid: u32,
+    #[serde(flatten)]
+    fields: Fields,
+    kernel_stack: Option<Vec<Frame<'a>>>,
+    user_stack: Option<Vec<Frame<'a>>>,
+}
+
+fn list_of(fields: Vec<Field>) -> DataType {
+    let item = Field::new("item", DataType::Struct(fields.into()), false);
+    DataType::List(item.into())
+}
+
+// As in `json`, for each tool
+fn value_fields(tool: &str) -> Vec<Field> {
+    use DataType::*;
+    let field = |name, data_type| Field::new(name, data_type, false);
+    match tool {
+        // Already formatted, so maybe not a number
+        "cpu_pct" | "mem_pct" => vec![Field::new("value", Float64, true)],
+        "runq_len" => vec![
+            field("value", Float32),
+            field("cpu", UInt32),
+            field("max", UInt64),
+        ],
+        "cpu_migrate" => vec![
+            field("value", UInt64),
+            field("orig_cpu", UInt32),
+            field("dest_cpu", UInt32),
+        ],
+        "bio_stat" => vec![
+            field("value", Float32),
+            field("disk", Utf8),
+            field("bytes", UInt64),
+            field("in_flight", Int64),
+            field("reads", UInt64),
+            field("writes", UInt64),
+            field("discards", UInt64),
+            field("flushes", UInt64),
+        ],
+        "bio_lat" => vec![
+            field("value", UInt64),
+            field("q_lat_us", UInt64),
+            field("disk", Utf8),
+            field("op", Utf8),
+            field("flags", Utf8),
+            field("rwbs", Utf8),
+            field("sector", UInt64),
+            field("len", UInt32),
+        ],
+        "fs_lat" => vec![
+            field("value", UInt64),
+            field("file", Utf8),
+            field("op", Utf8),
+            field("size", Int64),
+            field("offset", Int64),
+        ],
+        // Addresses as text, since the json decoder can't read binary columns
+        "tcp_pkt_lat" => vec![
+            field("value", UInt64),
+            field("saddr", Utf8),
+            field("daddr", Utf8),
+            field("sport", UInt16),
+            field("dport", UInt16),
+        ],
+        "func_lat" => vec![field("value", UInt64), field("func", Utf8)],
+        "func_lat_hist" => vec![
+            field("value", UInt64),
+            field("func", Utf8),
+            field(
+                "slots",
+                list_of(vec![
+                    field("lo_us", UInt64),
+                    field("hi_us", UInt64),
+                    field("count", UInt64),
+                ]),
+            ),
+        ],
+        // Latencies, and samples of stacks
+        _ => vec![field("value", UInt64)],
+    }
+}
+
+fn schema(tool: &str) -> Schema {
+    use DataType::*;
+    let frame = list_of(vec![
+        Field::new("addr", UInt64, false),
+        Field::new("symbol", Utf8, true),
+        Field::new("offset", UInt64, false),
+        Field::new("object", Utf8, true),
+    ]);
+    let mut fields = vec![
+        Field::new(
+            "time",
+            Timestamp(TimeUnit::Microsecond, Some("+00:00".into())),
+            false,
+        ),
+        Field::new("task", Utf8, false),
+        Field::new("pid", UInt32, false),
+    ];
+    fields.extend(value_fields(tool));
+    fields.push(Field::new("kernel_stack", frame.clone(), true));
+    fields.push(Field::new("user_stack", frame, true));
+    Schema::new(fields)
+}
+
+enum File {
+    Ipc(arrow_ipc::writer::FileWriter<std::fs::File>),
+    Parquet(parquet::arrow::ArrowWriter<std::fs::File>),
+}
+
+struct Writer {
+    file: File,
+    // Rows, until they're written as a batch
+    decoder: arrow_json::reader::Decoder,
+    rows: usize,
+}
+
+impl Writer {
+    fn create(
+        dir: &std::path::Path,
+        tool: &str,
+        parquet: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let schema = std::sync::Arc::new(schema(tool));
+        let file = match parquet {
+            true => {
+                let props = parquet::file::properties::WriterProperties::builder()
+                    .set_compression(parquet::basic::Compression::SNAPPY)
+                    .build();
+                let file = std::fs::File::create(dir.join(format!("{tool}.parquet")))?;
+                File::Parquet(parquet::arrow::ArrowWriter::try_new(
+                    file,
+                    schema.clone(),
+                    Some(props),
+                )?)
+            }
+            false => {
+                let file = std::fs::File::create(dir.join(format!("{tool}.arrow")))?;
+                File::Ipc(arrow_ipc::writer::FileWriter::try_new(file, &schema)?)
+            }
+        };
+        let decoder = arrow_json::ReaderBuilder::new(schema)
+            .with_batch_size(BATCH_ROWS)
+            .build_decoder()?;
+        Ok(Self {
+            file,
+            decoder,
+            rows: 0,
+        })
+    }
+
+    fn write_batch(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(batch) = self.decoder.flush()? {
+            match &mut self.file {
+                File::Ipc(w) => w.write(&batch)?,
+                File::Parquet(w) => {
+                    w.write(&batch)?;
+                    // Ends the row group
+                    w.flush()?;
+                }
+            }
+        }
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_batch()?;
+        match self.file {
+            File::Ipc(mut w) => w.finish()?,
+            File::Parquet(w) => {
+                w.close()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Output {
+    dir: std::path::PathBuf,
+    parquet: bool,
+    writers: std::collections::HashMap<String, Writer>,
+}
+
+static OUTPUT: std::sync::Mutex<Option<Output>> = std::sync::Mutex::new(None);
+
+// Into a directory, which is created if it doesn't exist. Unless buffered,
+// each tool's rows are written every reporting interval, from now on.
+pub fn init(
+    dir: &std::path::Path,
+    parquet: bool,
+    buffered: bool,
+    interval_ms: u64,
+) -> Result<(), std::io::Error> {
+    std::fs::create_dir_all(dir)?;
+    *OUTPUT.lock().unwrap() = Some(Output {
+        dir: dir.to_path_buf(),
+        parquet,
+        writers: std::collections::HashMap::new(),
+    });
+    if !buffered {
+        let interval = std::time::Duration::from_millis(interval_ms);
+        tokio::spawn(async move {
+            let mut ticks =
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticks.tick().await;
+                write_batches();
+            }
+        });
+    }
+    Ok(())
+}
+
+// Of every tool with rows, including those which have gone quiet
+fn write_batches() {
+    let mut output = OUTPUT.lock().unwrap();
+    let Some(output) = output.as_mut() else {
+        return;
+    };
+    for (tool, writer) in output.writers.iter_mut() {
+        if writer.rows == 0 {
+            continue;
+        }
+        if let Err(e) = writer.write_batch() {
+            log::error!("Error writing events of {tool}: {e}");
+        }
+    }
+}
+
+fn frames(frames: &[flaregun::Frame]) -> Vec<Frame<'_>> {
+    frames
+        .iter()
+        .map(|f| Frame {
+            addr: f.addr,
+            symbol: f.symbol.as_deref(),
+            offset: f.offset,
+            object: f.object.as_deref(),
+        })
+        .collect()
+}
+
+pub fn write<Value: crate::json::Fields>(tool: &str, event: &flaregun::Event<Value>) {
+    let mut output = OUTPUT.lock().unwrap();
+    let Some(output) = output.as_mut() else {
+        return;
+    };
+    let writer = match output.writers.entry(tool.to_string()) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            match Writer::create(&output.dir, tool, output.parquet) {
+                Ok(writer) => entry.insert(writer),
+                Err(e) => {
+                    log::error!("Error creating a file for {tool}: {e}");
+                    return;
+                }
+            }
+        }
+    };
+    let row = Row {
+        time: crate::start_time().timestamp_micros() + event.time.as_micros() as i64,
+        task: crate::bytes_to_str(&event.task),
+        pid: event.pid,
+        fields: event.value.fields(),
+        kernel_stack: event.stack.as_ref().map(|stack| frames(&stack.kernel)),
+        user_stack: event.stack.as_ref().map(|stack| frames(&stack.user)),
+    };
+    if let Err(e) = writer.decoder.serialize(&[row]) {
+        log::error!("Error writing an event of {tool}: {e}");
+        return;
+    }
+    writer.rows += 1;
+    if writer.rows >= BATCH_ROWS {
+        if let Err(e) = writer.write_batch() {
+            log::error!("Error writing events of {tool}: {e}");
+        }
+    }
+}
+
+// Writes what's left, and the files' footers, without which they're unreadable
+pub fn finish() {
+    let Some(output) = OUTPUT.lock().unwrap().take() else {
+        return;
+    };
+    for (tool, writer) in output.writers {
+        if let Err(e) = writer.finish() {
+            log::error!("Error finishing the file of {tool}: {e}");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// Events as JSON, one object per line. Each has the fields common to every tool
+// (tool, time, task, pid and value), then those specific to its tool, then its
+// stacks, if taken.
+
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+pub enum Time {
+    // For the microsecond time formats
+    Usecs(u64),
+    Formatted(String),
+}
+
+#[derive(serde::Serialize)]
+pub struct Event<'a, Fields> {
+    pub tool: &'a str,
+    pub time: Time,
+    pub task: &'a str,
+    pub pid: u32,
+    #[serde(flatten)]
+    pub fields: Fields,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_stack: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_stack: Option<Vec<String>>,
+}
+
+// The value, and anything else tool-specific
+pub trait Fields {
+    type Fields: serde::Serialize;
+    fn fields(&self) -> Self::Fields;
+}
+
+#[derive(serde::Serialize)]
+pub struct Value<T> {
+    value: T,
+}
+
+// Latencies (us), and samples of stacks, of tools with nothing else to them
+impl Fields for u64 {
+    type Fields = Value<u64>;
+    fn fields(&self) -> Self::Fields {
+        Value { value: *self }
+    }
+}
+
+// Cpu and memory utilization (%), which are already formatted
+impl Fields for String {
+    type Fields = Value<Option<f64>>;
+    fn fields(&self) -> Self::Fields {
+        Value {
+            value: self.parse().ok(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct QueueLen {
+    value: f32,
+    cpu: u32,
+    max: u64,
+}
+
+impl Fields for flaregun::QueueLen {
+    type Fields = QueueLen;
+    fn fields(&self) -> Self::Fields {
+        QueueLen {
+            value: self.avg,
+            cpu: self.cpu,
+            max: self.max,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct Migrations {
+    value: u64,
+    orig_cpu: u32,
+    dest_cpu: u32,
+}
+
+impl Fields for flaregun::Migrations {
+    type Fields = Migrations;
+    fn fields(&self) -> Self::Fields {
+        Migrations {
+            value: self.count,
+            orig_cpu: self.orig_cpu,
+            dest_cpu: self.dest_cpu,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct DiskStat {
+    value: f32,
+    disk: String,
+    bytes: u64,
+    in_flight: i64,
+    reads: u64,
+    writes: u64,
+    discards: u64,
+    flushes: u64,
+}
+
+impl Fields for flaregun::DiskStat {
+    type Fields = DiskStat;
+    fn fields(&self) -> Self::Fields {
+        DiskStat {
+            value: self.iops,
+            disk: self.disk(),
+            bytes: self.bytes,
+            in_flight: self.in_flight,
+            reads: self.reads,
+            writes: self.writes,
+            discards: self.discards,
+            flushes: self.flushes,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct FuncCall {
+    value: u64,
+    func: String,
+}
+
+impl Fields for flaregun::FuncCall {
+    type Fields = FuncCall;
+    fn fields(&self) -> Self::Fields {
+        FuncCall {
+            value: self.lat_us,
+            func: self.func.clone(),
+        }
+    }
+}
+
+// The calls which took [lo_us, hi_us) microseconds
+#[derive(serde::Serialize)]
+pub struct Slot {
+    lo_us: u64,
+    hi_us: u64,
+    count: u64,
+}
+
+#[derive(serde::Serialize)]
+pub struct FuncHist {
+    value: u64,
+    func: String,
+    // Only those with calls
+    slots: Vec<Slot>,
+}
+
+impl Fields for flaregun::FuncHist {
+    type Fields = FuncHist;
+    fn fields(&self) -> Self::Fields {
+        let slots = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(slot, count)| {
+                let us = flaregun::FuncHist::slot_range(slot);
+                Slot {
+                    lo_us: us.start,
+                    hi_us: us.end,
+                    count: *count,
+                }
+            })
+            .collect();
+        FuncHist {
+            value: self.count(),
+            func: self.func.clone(),
+            slots,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct Bio {
+    value: u64,
+    // Zero unless traced from the request's insertion into the queue
+    q_lat_us: u64,
+    disk: String,
+    op: String,
+    flags: String,
+    rwbs: String,
+    sector: u64,
+    len: u32,
+}
+
+impl Fields for flaregun::Bio {
+    type Fields = Bio;
+    fn fields(&self) -> Self::Fields {
+        Bio {
+            value: self.lat_us,
+            q_lat_us: self.q_lat_us,
+            disk: self.disk(),
+            op: self.op.to_string(),
+            flags: self.flags.to_string(),
+            rwbs: self.rwbs(),
+            sector: self.sector,
+            len: self.len,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct FsCall {
+    value: u64,
+    file: String,
+    op: String,
+    size: i64,
+    offset: i64,
+}
+
+impl Fields for flaregun::FsCall {
+    type Fields = FsCall;
+    fn fields(&self) -> Self::Fields {
+        FsCall {
+            value: self.lat_us,
+            file: self.file.clone(),
+            op: self.op.to_string(),
+            size: self.size,
+            offset: self.offset,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct TcpPkt {
+    value: u64,
+    saddr: String,
+    daddr: String,
+    sport: u16,
+    dport: u16,
+}
+
+impl Fields for flaregun::TcpPkt {
+    type Fields = TcpPkt;
+    fn fields(&self) -> Self::Fields {
+        TcpPkt {
+            value: self.lat_us,
+            saddr: self.saddr.to_string(),
+            daddr: self.daddr.to_string(),
+            sport: self.sport,
+            dport: self.dport,
+        }
+    }
+}
@@ -1,13 +1,43 @@
 // SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
 use clap::Parser;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod json;
+mod metrics;
+#[cfg(feature = "otlp")]
+mod otlp;
 mod outf;
+mod record;
+mod top;
+mod trace;
 extern crate flaregun;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Mode {
+    /// A live, full-screen view of each tool's rates and percentiles, the tasks
+    /// with the most latency, and cpu and memory utilization
+    Top,
+    /// Events in a binary recording ('--output-file'), to be replayed later
+    Record,
+    /// Events from a recording, in any output format
+    Replay,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 enum OutputFormat {
     Columnar,
     Csv,
     Json,
+    Folded,
+    ChromeTrace,
+    InfluxLine,
+    Statsd,
+    #[cfg(feature = "arrow")]
+    Arrow,
+    #[cfg(feature = "arrow")]
+    Parquet,
+    #[cfg(feature = "otlp")]
+    Otlp,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
@@ -19,7 +49,7 @@ enum TimeFormat {
     DurationHhMmSsMss,
     /// Duration since this program's start, in microseconds. Same as Usecs.
     DurationUsecs,
-    /// The current, utc system time, in an ISO 8601 format, with microsecond precision.
+    /// The utc system time of the event, in an ISO 8601 format, with microsecond precision.
     /// Same as Iso8601.
     TimeIso8601,
     HhMmSs,
@@ -28,6 +58,145 @@ enum TimeFormat {
     Iso8601,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum BioOp {
+    Read,
+    Write,
+    Flush,
+    Discard,
+    SecureErase,
+    WriteZeroes,
+}
+
+impl From<BioOp> for flaregun::BioOp {
+    fn from(op: BioOp) -> Self {
+        match op {
+            BioOp::Read => flaregun::BioOp::Read,
+            BioOp::Write => flaregun::BioOp::Write,
+            BioOp::Flush => flaregun::BioOp::Flush,
+            BioOp::Discard => flaregun::BioOp::Discard,
+            BioOp::SecureErase => flaregun::BioOp::SecureErase,
+            BioOp::WriteZeroes => flaregun::BioOp::WriteZeroes,
+        }
+    }
+}
+
+// Anything tool-specific, in columns after the value (for csv and columnar
+// output, where json has a field for each)
+trait Columns {
+    // Names, and widths when columnar
+    const COLUMNS: &'static [(&'static str, usize)] = &[];
+    // In the order of their names
+    fn columns(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl Columns for u64 {}
+impl Columns for String {}
+
+impl Columns for flaregun::QueueLen {
+    const COLUMNS: &'static [(&'static str, usize)] = &[("cpu", 4), ("max", 4)];
+    fn columns(&self) -> Vec<String> {
+        vec![self.cpu.to_string(), self.max.to_string()]
+    }
+}
+
+impl Columns for flaregun::Migrations {
+    const COLUMNS: &'static [(&'static str, usize)] = &[("orig_cpu", 8), ("dest_cpu", 8)];
+    fn columns(&self) -> Vec<String> {
+        vec![self.orig_cpu.to_string(), self.dest_cpu.to_string()]
+    }
+}
+
+impl Columns for flaregun::Bio {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("disk", 10),
+        ("rwbs", 6),
+        ("q_lat_us", 10),
+        ("sector", 12),
+        ("len", 8),
+    ];
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.disk(),
+            self.rwbs(),
+            self.q_lat_us.to_string(),
+            self.sector.to_string(),
+            self.len.to_string(),
+        ]
+    }
+}
+
+impl Columns for flaregun::DiskStat {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("disk", 10),
+        ("bytes", 12),
+        ("in_flight", 9),
+        ("reads", 8),
+        ("writes", 8),
+        ("discards", 8),
+        ("flushes", 8),
+    ];
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.disk(),
+            self.bytes.to_string(),
+            self.in_flight.to_string(),
+            self.reads.to_string(),
+            self.writes.to_string(),
+            self.discards.to_string(),
+            self.flushes.to_string(),
+        ]
+    }
+}
+
+impl Columns for flaregun::FsCall {
+    const COLUMNS: &'static [(&'static str, usize)] =
+        &[("file", 16), ("op", 6), ("size", 10), ("offset", 12)];
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.file.clone(),
+            self.op.to_string(),
+            self.size.to_string(),
+            self.offset.to_string(),
+        ]
+    }
+}
+
+impl Columns for flaregun::TcpPkt {
+    const COLUMNS: &'static [(&'static str, usize)] =
+        &[("saddr", 16), ("sport", 6), ("daddr", 16), ("dport", 6)];
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.saddr.to_string(),
+            self.sport.to_string(),
+            self.daddr.to_string(),
+            self.dport.to_string(),
+        ]
+    }
+}
+
+impl Columns for flaregun::FuncCall {
+    const COLUMNS: &'static [(&'static str, usize)] = &[("func", 24)];
+    fn columns(&self) -> Vec<String> {
+        vec![self.func.clone()]
+    }
+}
+
+// Like "vfs_read" and "8-16us:3 16-32us:10"
+impl Columns for flaregun::FuncHist {
+    const COLUMNS: &'static [(&'static str, usize)] = &[("func", 24), ("hist", 0)];
+    fn columns(&self) -> Vec<String> {
+        let mut hist = Vec::new();
+        for (slot, count) in self.slots.iter().enumerate().filter(|(_, c)| **c > 0) {
+            let us = flaregun::FuncHist::slot_range(slot);
+            hist.push(format!("{}-{}us:{count}", us.start, us.end));
+        }
+        vec![self.func.clone(), hist.join(" ")]
+    }
+}
+
 #[derive(Debug, Parser)]
 #[clap(
     version,
@@ -39,12 +208,17 @@ Allows tracing of:
 - Run queue scheduling latency
 - File system latency
 - TCP packet latency
+- Function latency, in programs, libraries and the kernel
 
 And monitoring of:
 - CPU utilization %
 - Virtual memory utilization %
+- Run queue length, per cpu
+- CPU migrations, per process
+- Block i/o throughput, per disk and process
+- Where cpu time is spent, as sampled stacks
 
-These metrics can be exported in a columnar, CSV, or JSON format.
+These metrics can be exported in a columnar, CSV, JSON, or folded stack format.
 
 When written as a CSV file, the output may be plotted using `fl-plot`:
 ```sh
@@ -57,6 +231,22 @@ The plot is a standalone HTML file which can be opened in a browser.
 "#
 )]
 struct Cli {
+    /// Show events as they happen (by default), a live view of them ('top'),
+    /// or record them to replay later ('record' and 'replay')
+    ///
+    /// In the live view ('fl top'), the tools are still chosen with their options,
+    /// e.g. 'fl top --rq-lat --fs-lat', and it's refreshed every reporting interval.
+    /// Cpu and memory utilization are always monitored.
+    /// Keys: 's' to sort tasks, 'p' and 'c' to filter by pid or comm, 'q' to quit.
+    ///
+    /// A recording is binary, smaller and cheaper to write than any output format,
+    /// e.g. 'fl record --all -o trace.flr', and is replayed in any output format,
+    /// e.g. 'fl replay trace.flr -f csv'.
+    #[arg(value_enum, verbatim_doc_comment)]
+    mode: Option<Mode>,
+    /// The recording to replay, from 'fl record'
+    #[arg(required_if_eq("mode", "replay"))]
+    recording: Option<std::path::PathBuf>,
     /// Enable all tracing and monitoring tools.
     #[arg(long, short)]
     all: bool,
@@ -78,6 +268,40 @@ struct Cli {
     /// Enable virtual memory utilization % monitoring
     #[arg(long)]
     mem_pct: bool,
+    /// Enable run queue length monitoring, sampled on each cpu
+    #[arg(long)]
+    runq_len: bool,
+    /// Enable cpu migration monitoring, counted for each process
+    #[arg(long)]
+    cpu_migrate: bool,
+    /// Enable block i/o throughput monitoring, for each disk and process
+    #[arg(long)]
+    bio_stat: bool,
+    /// Enable cpu profiling, counting the stacks sampled on each cpu
+    ///
+    /// Stacks are reported every interval, with how many times they were sampled.
+    /// See the folded output format ('-f folded') for use with flame graphs.
+    #[arg(long, verbatim_doc_comment)]
+    profile: bool,
+    /// Enable latency tracing of a function in a program or library
+    ///
+    /// Given as <PATH>:<SYMBOL>, e.g. '/usr/lib/libssl.so.3:SSL_read'.
+    /// Libraries may be given by name alone, e.g. 'libc.so.6:malloc'.
+    /// The latency is from the function's entry to its return, per thread.
+    #[arg(long, value_name = "PATH:SYMBOL", verbatim_doc_comment)]
+    uprobe: Option<flaregun::Uprobe>,
+    /// Enable latency tracing of a kernel function, or of those matching a glob
+    ///
+    /// E.g. 'vfs_read' or 'ext4_*'.
+    /// Each call is traced, unless '--func-hist' is given.
+    #[arg(long, value_name = "GLOB", verbatim_doc_comment)]
+    func: Option<String>,
+    /// Report the latency of kernel functions ('--func') as a histogram
+    ///
+    /// Counts calls by their latency, in power-of-two buckets of microseconds,
+    /// for each function, every reporting interval.
+    #[arg(long, requires = "func", verbatim_doc_comment)]
+    func_hist: bool,
     /// Process ID to trace, or 0 for everything
     ///
     /// +--process-A-+ --(fork)-> +--process-B-+ --(thread)-> +--process-B-+
@@ -104,6 +328,8 @@ struct Cli {
     /// - '--rq-lat'
     /// - '--fs-lat'
     /// - '--tcp-pkt-lat'
+    /// - '--uprobe'
+    /// - '--func', unless '--func-hist'
     #[arg(long, short = 'l', default_value = "10000", verbatim_doc_comment)]
     min_lat_us: u64,
     /// Trace block i/o latency higher than this value
@@ -118,13 +344,67 @@ struct Cli {
     /// Trace TCP packet latency higher than this value
     #[arg(long, default_value = None, conflicts_with = "min_lat_us")]
     min_tcp_pkt_lat_us: Option<u64>,
+    /// Trace function latency ('--uprobe') higher than this value
+    #[arg(long, default_value = None, conflicts_with = "min_lat_us")]
+    min_uprobe_lat_us: Option<u64>,
+    /// Trace kernel function latency ('--func') higher than this value
+    ///
+    /// With '--func-hist', counts only calls higher than this value (by default,
+    /// all of them).
+    #[arg(long, default_value = None, conflicts_with = "min_lat_us")]
+    min_func_lat_us: Option<u64>,
+    /// Capture the kernel and user stacks of traced events
+    ///
+    /// Affects:
+    /// - '--bio-lat', the stack which submitted the request
+    /// - '--fs-lat', the stack which called into the file system
+    /// - '--rq-lat', the stack of the task which last kept the waiting task
+    ///   off of the cpu
+    ///
+    /// Shown after each event in the columnar format, one frame per line,
+    /// and as "kernel_stack" and "user_stack" arrays in the json format.
+    #[arg(long, verbatim_doc_comment)]
+    stacks: bool,
+    /// Trace only block i/o requests of this operation
+    ///
+    /// Affects:
+    /// - '--bio-lat'
+    ///
+    /// Requests are shown with their operation and flags, abbreviated
+    /// as with blktrace (e.g. "WS" for a synchronous write).
+    #[arg(long, verbatim_doc_comment)]
+    bio_op: Option<BioOp>,
+    /// Trace only block i/o on this disk
+    ///
+    /// Affects:
+    /// - '--bio-lat'
+    /// - '--bio-stat'
+    ///
+    /// Given as a name (nvme0n1, sda2), a device-mapper name (vg0-root),
+    /// a device file (/dev/disk/by-label/root) or as major:minor (259:0).
+    /// Partitions are traced as the whole disk they're on.
+    #[arg(long, verbatim_doc_comment)]
+    disk: Option<String>,
     /// For monitoring tools, stats will be reported at this interval
     ///
     /// Affects:
     /// - '--cpu-pct'
     /// - '--mem-pct'
+    /// - '--runq-len'
+    /// - '--cpu-migrate'
+    /// - '--bio-stat'
+    /// - '--func-hist'
+    /// - '--profile'
     #[arg(long, short = 'i', default_value = "1000", verbatim_doc_comment)]
     reporting_interval_ms: u64,
+    /// Sample each cpu at this frequency
+    ///
+    /// Affects:
+    /// - '--profile'
+    ///
+    /// Odd, by default, to avoid sampling in lockstep with periodic activity.
+    #[arg(long, default_value = "99", verbatim_doc_comment)]
+    sample_freq_hz: u64,
     /// Some output styles are better for humans (columnar), others for machines
     ///
     /// - columnar
@@ -132,24 +412,96 @@ struct Cli {
     /// - csv
     ///   cpu_pct,101459,systemd,1,0.00
     /// - json
-    ///   {"tool":"cpu_pct","time":"101363","task":"systemd","pid":1,"value":0.00}
+    ///   {"tool":"cpu_pct","time":101363,"task":"systemd","pid":1,"value":0.0}
+    /// - folded
+    ///   systemd;main;read;vfs_read_[k] 12
+    /// - chrome-trace
+    ///   {"args":{"value":0.0},"cat":"cpu_pct","name":"cpu_pct","ph":"C","pid":1,"ts":101363},
+    /// - influx-line
+    ///   cpu_pct,task=systemd,pid=1 value=0.0 1700000000101363000
+    /// - statsd
+    ///   cpu_pct,task=systemd,pid=1:0.0|g
+    /// - arrow, parquet
+    ///   <output-file>/cpu_pct.arrow, <output-file>/cpu_pct.parquet
+    /// - otlp
+    ///   (to an OpenTelemetry collector, see '--otlp-endpoint')
+    ///
+    /// The folded format is for flame graphs, and only shows events with stacks
+    /// ('--profile', or '--stacks' with latency tracing). Their value is the
+    /// weight of the stack, i.e. a count of samples, or a latency.
+    ///
+    /// The chrome-trace format opens in Perfetto (ui.perfetto.dev), with latencies
+    /// as slices on their task's track, and the monitors' values as counters.
+    /// Without a header, it may be appended to an existing trace.
+    ///
+    /// The influx-line (InfluxDB line protocol) and statsd formats are for
+    /// Telegraf, with the tool as the measurement, the task, pid and e.g. disk
+    /// as tags, and the value and e.g. queue latency as fields (in statsd, as
+    /// their own metrics, like bio_lat.q_lat_us). Statsd latencies are timings
+    /// in milliseconds, and statsd has no times. See '--output-endpoint'.
+    ///
+    /// Arrow (IPC) and parquet files are written into a directory ('--output-file'),
+    /// one for each tool, with columns of the json fields, times as timestamps,
+    /// and stacks as lists of frames. Buffered, each batch (or row group) has up
+    /// to 65536 events, otherwise one is written every reporting interval.
+    ///
+    /// With otlp, each event is a log record, with the json fields as attributes.
+    /// Latencies are histograms of each tool (e.g. flaregun.rq_lat, in us), and
+    /// the monitors' values are gauges, or sums of counts (e.g. flaregun.cpu_pct,
+    /// flaregun.bio_stat.reads), by task and pid. Both are exported every
+    /// reporting interval.
     #[arg(long, short = 'f', default_value = "columnar", verbatim_doc_comment)]
     output_format: OutputFormat,
-    /// Output format for the duration since this program's start or the current time.
+    /// Output format for the duration since this program's start or the time of events
+    ///
+    /// Events are timed by the kernel (when they happened, rather than when they
+    /// were received), and converted to the system's time by when the kernel's
+    /// clock started. Monitoring tools' events are timed when they're reported.
     #[arg(long, short = 't', default_value = "iso8601", verbatim_doc_comment)]
     time_format: TimeFormat,
     /// Deprecated: Use '--time-format' instead
     #[arg(long, default_value = None, verbatim_doc_comment)]
     duration_format: Option<TimeFormat>,
     /// Write events to this file, if present, or to standard output if not given
-    #[arg(long, short = 'o')]
+    #[arg(long, short = 'o', required_if_eq("mode", "record"))]
     output_file: Option<std::path::PathBuf>,
+    /// Send events to this local endpoint, instead of a file
+    ///
+    /// Given as udp://<host>:<port> or tcp://<host>:<port>, e.g. to Telegraf's
+    /// socket_listener (influx-line) or statsd (statsd) input.
+    /// Over udp, events are sent in datagrams of up to 1400 bytes.
+    #[arg(long, conflicts_with = "output_file", verbatim_doc_comment)]
+    output_endpoint: Option<outf::Endpoint>,
+    /// Export to the OpenTelemetry collector at this endpoint
+    ///
+    /// Affects:
+    /// - '-f otlp'
+    ///
+    /// By default, http://localhost:4317 with grpc, or http://localhost:4318 with
+    /// http-protobuf, where requests are sent to <endpoint>/v1/logs and
+    /// <endpoint>/v1/metrics.
+    #[cfg(feature = "otlp")]
+    #[arg(long, verbatim_doc_comment)]
+    otlp_endpoint: Option<String>,
+    /// Export to the OpenTelemetry collector with this protocol
+    #[cfg(feature = "otlp")]
+    #[arg(long, default_value = "grpc", verbatim_doc_comment)]
+    otlp_protocol: otlp::Protocol,
     /// Use buffered writes for events
     ///
     /// Can increase performance.
     /// Send a SIGINT to flush the buffer and exit.
     #[arg(long, short = 'b', default_value = "true", verbatim_doc_comment)]
     buffered: bool,
+    /// Show (or record) the events of all tools in order of their times, holding
+    /// each for this long before it's shown
+    ///
+    /// Events are received some time after they happen, and each tool's on their
+    /// own, so are otherwise shown in the order they're received. Events received
+    /// later than this after they happened may still be out of order.
+    /// Has no effect on replays.
+    #[arg(long, verbatim_doc_comment)]
+    merge_window_ms: Option<u64>,
     /// Omit the header (tool/time/task/pid/value) as the first line of output
     ///
     /// Has no effect when the output format ('-f, --output-format') is json.
@@ -181,8 +533,24 @@ fn duration_to_usecs_string(duration: std::time::Duration) -> String {
     format!("{us}")
 }
 
-fn time_as_iso_8601_string() -> String {
-    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+static START_TIME: std::sync::OnceLock<chrono::DateTime<chrono::Utc>> = std::sync::OnceLock::new();
+
+// By the system's clock, when this program started, or when a recording did,
+// which events' times (by the kernel's clock) are since
+fn start_time() -> chrono::DateTime<chrono::Utc> {
+    *START_TIME.get_or_init(|| flaregun::time::wall_clock(std::time::Duration::ZERO).into())
+}
+
+// Before any times are shown
+fn set_start_time(us: i64) {
+    if let Some(time) = chrono::DateTime::from_timestamp_micros(us) {
+        let _ = START_TIME.set(time);
+    }
+}
+
+fn time_as_iso_8601_string(duration: std::time::Duration) -> String {
+    let time = start_time() + chrono::TimeDelta::from_std(duration).unwrap_or_default();
+    time.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
 }
 
 fn bytes_to_str(bytes: &[u8]) -> &str {
@@ -192,11 +560,83 @@ fn bytes_to_str(bytes: &[u8]) -> &str {
         .trim()
 }
 
+// An event of any tool, to be shown (or recorded) in order with the others'
+struct Merged {
+    time: std::time::Duration,
+    emit: Box<dyn FnOnce() + Send>,
+}
+
+impl flaregun::Timed for Merged {
+    fn time(&self) -> std::time::Duration {
+        self.time
+    }
+}
+
+type Merging = flaregun::Merge<futures::channel::mpsc::UnboundedReceiver<Merged>>;
+
 async fn forever() {
     tokio::sync::Semaphore::new(0).acquire().await.ok();
 }
 
-fn show_header(opts: &Cli) {
+// Quoted if it would otherwise be split, or its quotes taken as its end
+fn csv_field(s: &str) -> std::borrow::Cow<'_, str> {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")).into(),
+        false => s.into(),
+    }
+}
+
+// As their events are named
+fn enabled_tools(opts: &Cli) -> Vec<&'static str> {
+    let top_mode = opts.mode == Some(Mode::Top);
+    let tools = [
+        ("bio_lat", opts.all || opts.bio_lat),
+        ("fs_lat", opts.all || opts.fs_lat),
+        ("rq_lat", opts.all || opts.rq_lat),
+        ("tcp_pkt_lat", opts.all || opts.tcp_pkt_lat),
+        ("cpu_pct", opts.all || opts.cpu_pct || top_mode),
+        ("mem_pct", opts.all || opts.mem_pct || top_mode),
+        ("runq_len", opts.all || opts.runq_len),
+        ("cpu_migrate", opts.all || opts.cpu_migrate),
+        ("bio_stat", opts.all || opts.bio_stat),
+        ("profile", opts.all || opts.profile),
+        ("uprobe_lat", opts.uprobe.is_some()),
+        ("func_lat", opts.func.is_some() && !opts.func_hist),
+        ("func_lat_hist", opts.func_hist),
+    ];
+    tools
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(tool, _)| tool)
+        .collect()
+}
+
+// Of every tool, in a union, so that the header has a column for anything an
+// event might have (which is empty for events of other tools)
+fn tool_columns(tools: &[&str]) -> Vec<(&'static str, usize)> {
+    let mut columns: Vec<(&'static str, usize)> = Vec::new();
+    for tool in tools {
+        let tool_columns = match *tool {
+            "bio_lat" => flaregun::Bio::COLUMNS,
+            "fs_lat" => flaregun::FsCall::COLUMNS,
+            "tcp_pkt_lat" => flaregun::TcpPkt::COLUMNS,
+            "runq_len" => flaregun::QueueLen::COLUMNS,
+            "cpu_migrate" => flaregun::Migrations::COLUMNS,
+            "bio_stat" => flaregun::DiskStat::COLUMNS,
+            "func_lat" => flaregun::FuncCall::COLUMNS,
+            "func_lat_hist" => flaregun::FuncHist::COLUMNS,
+            _ => &[],
+        };
+        for column in tool_columns {
+            if !columns.iter().any(|(name, _)| name == &column.0) {
+                columns.push(*column);
+            }
+        }
+    }
+    columns
+}
+
+fn show_header(opts: &Cli, columns: &[(&'static str, usize)]) {
     use OutputFormat::*;
     macro_rules! printfn {
         ($($arg:tt)*) => {
@@ -207,16 +647,29 @@ fn show_header(opts: &Cli) {
         };
     }
     match opts.output_format {
-        Columnar => printfn!(
-            "{:<12} {:<13} {:<20} {:<8} {:<14}",
-            "tool",
-            "time",
-            "task",
-            "pid",
-            "value"
-        ),
-        Csv => printfn!("tool,time,task,pid,value"),
-        Json => (),
+        Columnar => {
+            let mut header = format!(
+                "{:<12} {:<13} {:<20} {:<8} {:<14}",
+                "tool", "time", "task", "pid", "value"
+            );
+            for (name, width) in columns {
+                header.push_str(&format!(" {name:<width$}"));
+            }
+            printfn!("{header}")
+        }
+        Csv => {
+            let mut header = "tool,time,task,pid,value".to_string();
+            for (name, _) in columns {
+                header.push_str(&format!(",{name}"));
+            }
+            printfn!("{header}")
+        }
+        ChromeTrace => printfn!("["),
+        Json | Folded | InfluxLine | Statsd => (),
+        #[cfg(feature = "arrow")]
+        Arrow | Parquet => (),
+        #[cfg(feature = "otlp")]
+        Otlp => (),
     }
 }
 
@@ -225,9 +678,10 @@ fn show_event<Value>(
     output_format: OutputFormat,
     time_format: TimeFormat,
     buffered: bool,
+    columns: &[(&'static str, usize)],
     event: &flaregun::Event<Value>,
 ) where
-    Value: std::fmt::Display,
+    Value: std::fmt::Display + Columns + json::Fields,
 {
     use OutputFormat::*;
     use TimeFormat::*;
@@ -235,7 +689,7 @@ fn show_event<Value>(
         DurationHhMmSs | HhMmSs => duration_to_hh_mm_ss_string(event.time),
         DurationHhMmSsMss | HhMmSsMss => duration_to_hh_mm_ss_mss_string(event.time),
         DurationUsecs | Usecs => duration_to_usecs_string(event.time),
-        TimeIso8601 | Iso8601 => time_as_iso_8601_string(),
+        TimeIso8601 | Iso8601 => time_as_iso_8601_string(event.time),
     };
     let t = bytes_to_str(&event.task);
     let p = event.pid;
@@ -248,47 +702,195 @@ fn show_event<Value>(
             }
         };
     }
+    // This tool's values in their columns, if any, and empty in the others'
+    let values = v.columns();
+    let x = columns.iter().map(|(name, width)| {
+        let value = Value::COLUMNS
+            .iter()
+            .position(|(n, _)| n == name)
+            .map(|i| values[i].as_str())
+            .unwrap_or_default();
+        (value, *width)
+    });
     match output_format {
-        Columnar => printfn!("{tool:<12} {d:<13} {t:<20} {p:<8} {v:<14}"),
-        Csv => printfn!("{tool},{d},{t},{p},{v}"),
-        Json => printfn!(r#"{{"tool":"{tool}","time":"{d}","task":"{t}","pid":{p},"value":{v}}}"#),
+        Columnar => {
+            let mut line = format!("{tool:<12} {d:<13} {t:<20} {p:<8} {v:<14}");
+            for (value, width) in x {
+                line.push_str(&format!(" {value:<width$}"));
+            }
+            printfn!("{line}")
+        }
+        Csv => {
+            let mut line = format!("{tool},{d},{},{p},{v}", csv_field(t));
+            for (value, _) in x {
+                line.push_str(&format!(",{}", csv_field(value)));
+            }
+            printfn!("{line}")
+        }
+        Folded => {
+            if let Some(stack) = &event.stack {
+                printfn!("{} {v}", stack.folded(t))
+            }
+        }
+        Json => {
+            let frames =
+                |frames: &[flaregun::Frame]| frames.iter().map(|f| f.to_string()).collect();
+            let time = match time_format {
+                DurationUsecs | Usecs => json::Time::Usecs(event.time.as_micros() as u64),
+                _ => json::Time::Formatted(d),
+            };
+            let json = json::Event {
+                tool,
+                time,
+                task: t,
+                pid: p,
+                fields: v.fields(),
+                kernel_stack: event.stack.as_ref().map(|stack| frames(&stack.kernel)),
+                user_stack: event.stack.as_ref().map(|stack| frames(&stack.user)),
+            };
+            match serde_json::to_string(&json) {
+                Ok(json) => printfn!("{json}"),
+                Err(e) => log::error!("Error writing JSON: {e}"),
+            }
+        }
+        ChromeTrace => {
+            if let Some(lines) = trace::lines(tool, event) {
+                printfn!("{lines}")
+            }
+        }
+        InfluxLine => {
+            if let Some(line) = metrics::influx_line(tool, event) {
+                printfn!("{line}")
+            }
+        }
+        Statsd => {
+            if let Some(lines) = metrics::statsd_lines(tool, event) {
+                printfn!("{lines}")
+            }
+        }
+        #[cfg(feature = "arrow")]
+        Arrow | Parquet => arrow::write(tool, event),
+        #[cfg(feature = "otlp")]
+        Otlp => otlp::write(tool, event),
+    }
+    // Innermost frame first, kernel frames before user frames
+    if let (Columnar, Some(stack)) = (output_format, &event.stack) {
+        for frame in &stack.kernel {
+            printfn!("    {frame}");
+        }
+        if !stack.kernel.is_empty() && !stack.user.is_empty() {
+            printfn!("    --");
+        }
+        for frame in &stack.user {
+            printfn!("    {frame}");
+        }
     }
 }
 
-async fn flaregun(opts: Cli) -> Result<(), Box<dyn std::error::Error>> {
+async fn flaregun(
+    opts: Cli,
+    merging: &mut Option<Merging>,
+) -> Result<(), Box<dyn std::error::Error>> {
     use flaregun::tool::Tool;
     use flaregun::BioLat;
+    use flaregun::BioStat;
+    use flaregun::CpuMigrate;
     use flaregun::CpuPct;
     use flaregun::FsLat;
+    use flaregun::FuncLat;
+    use flaregun::FuncLatHist;
     use flaregun::MemPct;
+    use flaregun::Profile;
     use flaregun::RqLat;
+    use flaregun::RunqLen;
     use flaregun::TcpPktLat;
+    use flaregun::UprobeLat;
     use futures::StreamExt;
+    let targ_dev = match &opts.disk {
+        Some(disk) => match flaregun::disk::dev(disk) {
+            Some(dev) => Some(flaregun::disk::whole_disk(dev)),
+            None => return Err(format!("No such disk: {disk}").into()),
+        },
+        None => None,
+    };
+    #[cfg(feature = "arrow")]
+    if let (OutputFormat::Arrow | OutputFormat::Parquet, Some(Mode::Replay) | None) =
+        (opts.output_format, opts.mode)
+    {
+        let m = "Arrow and parquet files need a directory to be written to ('--output-file')";
+        let dir = opts.output_file.as_ref().ok_or(m)?;
+        let parquet = opts.output_format == OutputFormat::Parquet;
+        arrow::init(dir, parquet, opts.buffered, opts.reporting_interval_ms)?;
+    }
+    #[cfg(feature = "otlp")]
+    if let (OutputFormat::Otlp, Some(Mode::Replay) | None) = (opts.output_format, opts.mode) {
+        let endpoint = opts.otlp_endpoint.as_deref();
+        otlp::init(opts.otlp_protocol, endpoint, opts.reporting_interval_ms)?;
+    }
+    if let (Some(Mode::Replay), Some(recording)) = (opts.mode, &opts.recording) {
+        return record::replay(&opts, recording);
+    }
+    let mode = opts.mode;
+    let tools = enabled_tools(&opts);
+    // For as long as the tools run
+    let columns: &'static [(&'static str, usize)] = tool_columns(&tools).leak();
+    let (merge_tx, merge_rx) = match opts.merge_window_ms {
+        Some(_) => {
+            let (tx, rx) = futures::channel::mpsc::unbounded::<Merged>();
+            (Some(tx), Some(rx))
+        }
+        None => (None, None),
+    };
     macro_rules! tool_task {
-        ($opt:ident, $opt_mlu:expr, $prog:ident) => {
+        ($opt:ident, $opt_mlu:expr, $prog:ident) => {{
+            let enabled = tools.contains(&stringify!($opt));
+            let cfg = flaregun::Cfg {
+                min_lat_us: $opt_mlu.unwrap_or(opts.min_lat_us),
+                targ_reporting_interval_ms: opts.reporting_interval_ms,
+                targ_pid: opts.pid,
+                targ_tgid: opts.tgid,
+                targ_dev: targ_dev.unwrap_or(0) as u64,
+                targ_filter_dev: targ_dev.is_some(),
+                targ_filter_cgroup: false,
+                targ_filter_queued: false,
+                targ_bio_op: opts.bio_op.map(Into::into),
+                targ_uprobe: opts.uprobe.clone(),
+                targ_func: opts.func.clone(),
+                targ_stacks: opts.stacks,
+                targ_sample_freq_hz: opts.sample_freq_hz,
+            };
+            let merging = merge_tx.clone();
             tokio::spawn(async move {
                 let time_format = opts.duration_format.unwrap_or(opts.time_format);
-                let cfg = flaregun::Cfg {
-                    min_lat_us: $opt_mlu.unwrap_or(opts.min_lat_us),
-                    targ_reporting_interval_ms: opts.reporting_interval_ms,
-                    targ_pid: opts.pid,
-                    targ_tgid: opts.tgid,
-                    targ_dev: 0,
-                    targ_filter_dev: false,
-                    targ_filter_cgroup: false,
-                    targ_filter_queued: false,
-                };
+                let (output_format, buffered) = (opts.output_format, opts.buffered);
                 log::trace!("cfg: {:?}", cfg);
-                if opts.all || opts.$opt {
+                if enabled {
+                    let id = match mode {
+                        Some(Mode::Record) => record::tool(stringify!($opt), &cfg),
+                        _ => 0,
+                    };
                     let mut prog = $prog::try_new(cfg)?;
-                    while let Some(event) = prog.next().await {
-                        show_event(
+                    let emit = move |event: &flaregun::Event<_>| match mode {
+                        Some(Mode::Top) => top::record(stringify!($opt), event),
+                        Some(Mode::Record) => record::event(id, event),
+                        _ => show_event(
                             stringify!($opt),
-                            opts.output_format,
+                            output_format,
                             time_format,
-                            opts.buffered,
-                            &event,
-                        );
+                            buffered,
+                            columns,
+                            event,
+                        ),
+                    };
+                    while let Some(event) = prog.next().await {
+                        match &merging {
+                            Some(tx) => {
+                                let time = event.time;
+                                let emit = Box::new(move || emit(&event));
+                                let _ = tx.unbounded_send(Merged { time, emit });
+                            }
+                            None => emit(&event),
+                        }
                     }
                 } else {
                     forever().await;
@@ -296,22 +898,56 @@ async fn flaregun(opts: Cli) -> Result<(), Box<dyn std::error::Error>> {
                 let m = "Task ended, but not because of the user";
                 Err(flaregun::tool::Error::Runtime(m))
             })
-        };
+        }};
     }
-    if !opts.no_header {
-        show_header(&opts);
+    match (mode, &opts.output_file) {
+        (Some(Mode::Record), Some(output_file)) => record::init(output_file)?,
+        (Some(Mode::Top), _) => (),
+        _ if !opts.no_header => show_header(&opts, columns),
+        _ => (),
     }
     if opts.just_header {
         return Ok(());
     }
     flaregun::must_bump_memlock_rlimit_once();
+    let live_view = async {
+        if mode == Some(Mode::Top) {
+            return top::run(opts.reporting_interval_ms).await;
+        }
+        forever().await;
+        Ok(())
+    };
+    // Events of all tools, in order, held in `merging` for what's left to be
+    // emitted if it's stopped
+    let merge_stage = async {
+        let (Some(rx), Some(ms)) = (merge_rx, opts.merge_window_ms) else {
+            return forever().await;
+        };
+        let merged = merging.insert(flaregun::Merge::new(
+            [rx],
+            std::time::Duration::from_millis(ms),
+        ));
+        while let Some(event) = merged.next().await {
+            (event.emit)();
+        }
+    };
     Ok(tokio::select! {
+        r = live_view => return Ok(r?),
+        () = merge_stage => return Ok(()),
         r = tool_task!(bio_lat, opts.min_bio_lat_us, BioLat) => r,
         r = tool_task!(fs_lat, opts.min_fs_lat_us, FsLat) => r,
         r = tool_task!(rq_lat, opts.min_rq_lat_us, RqLat) => r,
         r = tool_task!(tcp_pkt_lat, opts.min_tcp_pkt_lat_us, TcpPktLat) => r,
         r = tool_task!(cpu_pct, None, CpuPct) => r,
         r = tool_task!(mem_pct, None, MemPct) => r,
+        r = tool_task!(runq_len, None, RunqLen) => r,
+        r = tool_task!(cpu_migrate, None, CpuMigrate) => r,
+        r = tool_task!(bio_stat, None, BioStat) => r,
+        r = tool_task!(profile, None, Profile) => r,
+        r = tool_task!(uprobe_lat, opts.min_uprobe_lat_us, UprobeLat) => r,
+        r = tool_task!(func_lat, opts.min_func_lat_us, FuncLat) => r,
+        // Every call, by default, or the lower buckets would be empty
+        r = tool_task!(func_lat_hist, opts.min_func_lat_us.or(Some(0)), FuncLatHist) => r,
     }??)
 }
 
@@ -322,10 +958,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let _ = flaregun::time::prog_start();
     let opts = Cli::parse();
     let mut sigints = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
-    outf::init(&opts.output_file);
+    // Recordings, arrow and parquet files, and otlp exports are written on
+    // their own
+    match (opts.mode, opts.output_format) {
+        (Some(Mode::Record), _) => outf::init(&None),
+        #[cfg(feature = "arrow")]
+        (_, OutputFormat::Arrow | OutputFormat::Parquet) => outf::init(&None),
+        #[cfg(feature = "otlp")]
+        (_, OutputFormat::Otlp) => outf::init(&None),
+        _ => outf::init(&opts.output_file),
+    }
+    if let Some(endpoint) = &opts.output_endpoint {
+        outf::connect(endpoint)?;
+    }
+    let mut merging = None;
     let r = tokio::select! {
-        r = flaregun(opts) => r,
-        _ = sigints.recv() => Ok(outf::buf_flush()),
+        r = flaregun(opts, &mut merging) => r,
+        _ = sigints.recv() => {
+            // Events still held in the merge window
+            for event in merging.into_iter().flat_map(flaregun::Merge::drain) {
+                (event.emit)();
+            }
+            Ok(outf::buf_flush())
+        }
     };
+    record::flush();
+    #[cfg(feature = "arrow")]
+    arrow::finish();
+    #[cfg(feature = "otlp")]
+    otlp::finish().await;
     r
 }
@@ -0,0 +1,159 @@
+// Events as metrics, in the InfluxDB line protocol or StatsD, for Telegraf and
+// the like. The tool is the measurement (or the metric's name), with tags of
+// what an event is of (its task and pid, and e.g. its disk), and fields of what
+// was measured (its value, and e.g. its queue latency).
+
+pub struct Point {
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, serde_json::Number)>,
+}
+
+impl Point {
+    pub fn of<Value: crate::json::Fields>(event: &flaregun::Event<Value>) -> Option<Self> {
+        let mut tags = vec![
+            (
+                "task".to_string(),
+                crate::bytes_to_str(&event.task).to_string(),
+            ),
+            ("pid".to_string(), event.pid.to_string()),
+        ];
+        let mut fields = Vec::new();
+        let serde_json::Value::Object(values) = serde_json::to_value(event.value.fields()).ok()?
+        else {
+            return None;
+        };
+        for (name, value) in values {
+            match value {
+                // A run queue's cpu is what it's of
+                serde_json::Value::Number(n) if name == "cpu" => tags.push((name, n.to_string())),
+                serde_json::Value::Number(n) => fields.push((name, n)),
+                serde_json::Value::String(s) if !s.is_empty() => tags.push((name, s)),
+                // A function's calls, in each slot of a histogram
+                serde_json::Value::Array(slots) => {
+                    for slot in slots {
+                        let (Some(lo), Some(hi), Some(count)) = (
+                            slot["lo_us"].as_u64(),
+                            slot["hi_us"].as_u64(),
+                            slot["count"].as_u64(),
+                        ) else {
+                            continue;
+                        };
+                        fields.push((format!("slot_{lo}_{hi}us"), count.into()));
+                    }
+                }
+                // Unknown, e.g. a cpu utilization which isn't a number
+                _ => (),
+            }
+        }
+        Some(Self { tags, fields })
+    }
+}
+
+// Commas, spaces (and equals signs, except in measurements) are escaped
+fn escape(s: &str, chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if chars.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Like "rq_lat,task=kworker/0:1,pid=42 value=17i 1700000000000000000", where
+// the time is in nanoseconds since the epoch
+pub fn influx_line<Value: crate::json::Fields>(
+    tool: &str,
+    event: &flaregun::Event<Value>,
+) -> Option<String> {
+    let point = Point::of(event)?;
+    if point.fields.is_empty() {
+        return None;
+    }
+    let mut line = escape(tool, &[',', ' ']);
+    for (name, value) in &point.tags {
+        let (name, value) = (
+            escape(name, &[',', '=', ' ']),
+            escape(value, &[',', '=', ' ']),
+        );
+        line.push_str(&format!(",{name}={value}"));
+    }
+    let fields: Vec<String> = point
+        .fields
+        .iter()
+        .map(|(name, value)| {
+            let name = escape(name, &[',', '=', ' ']);
+            match value.is_f64() {
+                true => format!("{name}={value}"),
+                false => format!("{name}={value}i"),
+            }
+        })
+        .collect();
+    let ns = crate::start_time().timestamp_micros() * 1000 + event.time.as_nanos() as i64;
+    line.push_str(&format!(" {} {ns}", fields.join(",")));
+    Some(line)
+}
+
+// In microseconds
+pub fn is_latency(tool: &str, field: &str) -> bool {
+    field.ends_with("_us") || (field == "value" && tool.ends_with("_lat"))
+}
+
+// Counted over a reporting interval, otherwise a gauge (or a timing)
+pub fn is_count(tool: &str, field: &str) -> bool {
+    matches!(
+        (tool, field),
+        ("cpu_migrate" | "profile", "value")
+            | ("func_lat_hist", _)
+            | (
+                "bio_stat",
+                "bytes" | "reads" | "writes" | "discards" | "flushes"
+            )
+    )
+}
+
+// Characters with meaning in a line, in names and tags
+fn sanitize(s: &str) -> String {
+    s.replace([':', '|', ',', '=', ' ', '@', '#'], "_")
+}
+
+// Like "rq_lat,task=kworker/0:1,pid=42:0.017|ms", one for each field (the
+// value is the tool's, and the others are its own, like "bio_lat.q_lat_us"),
+// with tags as Telegraf takes them. Latencies are timings, in milliseconds.
+// StatsD has no times, so they're when the lines are received.
+pub fn statsd_lines<Value: crate::json::Fields>(
+    tool: &str,
+    event: &flaregun::Event<Value>,
+) -> Option<String> {
+    let point = Point::of(event)?;
+    let tags: String = point
+        .tags
+        .iter()
+        .map(|(name, value)| format!(",{}={}", sanitize(name), sanitize(value)))
+        .collect();
+    let mut lines = Vec::new();
+    for (field, value) in &point.fields {
+        let name = match field.as_str() {
+            "value" => sanitize(tool),
+            field => format!("{}.{}", sanitize(tool), sanitize(field)),
+        };
+        let Some(v) = value.as_f64() else {
+            continue;
+        };
+        match (is_latency(tool, field), is_count(tool, field)) {
+            (true, _) => lines.push(format!("{name}{tags}:{}|ms", v / 1000.0)),
+            (false, true) => lines.push(format!("{name}{tags}:{value}|c")),
+            // Which would otherwise be taken as a decrement
+            (false, false) if v < 0.0 => {
+                lines.push(format!("{name}{tags}:0|g"));
+                lines.push(format!("{name}{tags}:{value}|g"));
+            }
+            (false, false) => lines.push(format!("{name}{tags}:{value}|g")),
+        }
+    }
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join("\n")),
+    }
+}
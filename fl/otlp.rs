@@ -0,0 +1,674 @@
+// Events as OpenTelemetry (OTLP) logs and metrics, pushed to a collector over
+// gRPC or HTTP (protobuf), every reporting interval. Each event is a log record,
+// with the fields of the json output as its attributes. Latencies are
+// aggregated into histograms, and the monitors' values are gauges (or sums, of
+// counts), with the tags of `metrics` as their attributes.
+
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_client::LogsServiceClient;
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_client::MetricsServiceClient;
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use opentelemetry_proto::tonic::metrics::v1::{metric, number_data_point};
+use opentelemetry_proto::tonic::metrics::v1::{AggregationTemporality, Metric, ResourceMetrics};
+use opentelemetry_proto::tonic::metrics::v1::{Gauge, Histogram, ScopeMetrics, Sum};
+use opentelemetry_proto::tonic::metrics::v1::{HistogramDataPoint, NumberDataPoint};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Protocol {
+    /// To http://localhost:4317, by default
+    Grpc,
+    /// Protobuf over HTTP, to http://localhost:4318, by default
+    HttpProtobuf,
+}
+
+// Log records in each request, so that a replay's aren't all in one
+const LOG_RECORDS_PER_EXPORT: usize = 4096;
+
+// For each request, as a collector may accept a connection but never respond
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// Histograms' bounds, in microseconds, from 1us to ~16s, by powers of two
+const BOUNDS_US: [f64; 25] = {
+    let mut bounds = [0.0; 25];
+    let mut i = 0;
+    while i < bounds.len() {
+        bounds[i] = (1u64 << i) as f64;
+        i += 1;
+    }
+    bounds
+};
+
+// A metric's name, and its attributes
+type Key = (String, Vec<(String, String)>);
+
+// Times are in nanoseconds since the epoch, of the first and last events
+// in an interval
+struct Hist {
+    start: u64,
+    time: u64,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    buckets: Vec<u64>,
+}
+
+impl Hist {
+    fn new(time: u64) -> Self {
+        Self {
+            start: time,
+            time,
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            buckets: vec![0; BOUNDS_US.len() + 1],
+        }
+    }
+
+    fn add(&mut self, time: u64, value: f64) {
+        self.time = time;
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buckets[BOUNDS_US.partition_point(|bound| *bound < value)] += 1;
+    }
+
+    fn data_point(self, tags: &[(String, String)]) -> HistogramDataPoint {
+        HistogramDataPoint {
+            attributes: attributes(tags),
+            start_time_unix_nano: self.start,
+            time_unix_nano: self.time,
+            count: self.count,
+            sum: Some(self.sum),
+            bucket_counts: self.buckets,
+            explicit_bounds: BOUNDS_US.to_vec(),
+            min: Some(self.min),
+            max: Some(self.max),
+            ..Default::default()
+        }
+    }
+}
+
+// A gauge's last value, or a sum's total, in an interval
+struct Number {
+    start: u64,
+    time: u64,
+    value: f64,
+}
+
+impl Number {
+    fn data_point(self, tags: &[(String, String)]) -> NumberDataPoint {
+        NumberDataPoint {
+            attributes: attributes(tags),
+            start_time_unix_nano: self.start,
+            time_unix_nano: self.time,
+            value: Some(number_data_point::Value::AsDouble(self.value)),
+            ..Default::default()
+        }
+    }
+}
+
+// Since the last export
+#[derive(Default)]
+struct Pending {
+    logs: Vec<LogRecord>,
+    hists: std::collections::BTreeMap<Key, Hist>,
+    gauges: std::collections::BTreeMap<Key, Number>,
+    sums: std::collections::BTreeMap<Key, Number>,
+}
+
+#[derive(Clone)]
+enum Transport {
+    Grpc(tonic::transport::Channel),
+    // To a host:port, under a path, if any
+    Http { addr: String, path: String },
+}
+
+struct Output {
+    transport: Transport,
+    resource: Resource,
+    pending: Pending,
+}
+
+static OUTPUT: std::sync::Mutex<Option<Output>> = std::sync::Mutex::new(None);
+
+fn string(s: &str) -> AnyValue {
+    AnyValue {
+        value: Some(any_value::Value::StringValue(s.to_string())),
+    }
+}
+
+fn key_value(key: &str, value: AnyValue) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(value),
+    }
+}
+
+fn any_value(value: &serde_json::Value) -> Option<AnyValue> {
+    use any_value::Value::*;
+    let value = match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::Bool(b) => BoolValue(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => IntValue(i),
+            None => DoubleValue(n.as_f64()?),
+        },
+        serde_json::Value::String(s) => StringValue(s.clone()),
+        serde_json::Value::Array(values) => {
+            ArrayValue(opentelemetry_proto::tonic::common::v1::ArrayValue {
+                values: values.iter().filter_map(any_value).collect(),
+            })
+        }
+        serde_json::Value::Object(values) => {
+            KvlistValue(opentelemetry_proto::tonic::common::v1::KeyValueList {
+                values: key_values(values),
+            })
+        }
+    };
+    Some(AnyValue { value: Some(value) })
+}
+
+fn key_values(values: &serde_json::Map<String, serde_json::Value>) -> Vec<KeyValue> {
+    values
+        .iter()
+        .filter_map(|(key, value)| Some(key_value(key, any_value(value)?)))
+        .collect()
+}
+
+// Pids and cpus are integers
+fn attributes(tags: &[(String, String)]) -> Vec<KeyValue> {
+    tags.iter()
+        .map(|(key, value)| match (key.as_str(), value.parse()) {
+            ("pid" | "cpu", Ok(i)) => key_value(
+                key,
+                AnyValue {
+                    value: Some(any_value::Value::IntValue(i)),
+                },
+            ),
+            _ => key_value(key, string(value)),
+        })
+        .collect()
+}
+
+fn resource() -> Resource {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+    Resource {
+        attributes: vec![
+            key_value("service.name", string("flaregun")),
+            key_value("service.version", string(env!("CARGO_PKG_VERSION"))),
+            key_value("host.name", string(hostname.trim())),
+            key_value("os.type", string("linux")),
+            key_value("os.version", string(&crate::record::kernel_release())),
+        ],
+        ..Default::default()
+    }
+}
+
+fn scope() -> Option<InstrumentationScope> {
+    Some(InstrumentationScope {
+        name: "flaregun".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Default::default()
+    })
+}
+
+// Exports every reporting interval, from now on, to a collector at the endpoint
+// (or at the protocol's default)
+pub fn init(
+    protocol: Protocol,
+    endpoint: Option<&str>,
+    interval_ms: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let transport = match (protocol, endpoint) {
+        (Protocol::Grpc, endpoint) => {
+            let endpoint = endpoint.unwrap_or("http://localhost:4317").to_string();
+            Transport::Grpc(tonic::transport::Endpoint::from_shared(endpoint)?.connect_lazy())
+        }
+        (Protocol::HttpProtobuf, endpoint) => {
+            let endpoint = endpoint.unwrap_or("http://localhost:4318");
+            let m = "Only http:// endpoints are supported for OTLP over HTTP";
+            let rest = endpoint.strip_prefix("http://").ok_or(m)?;
+            let (addr, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            let addr = match addr.contains(':') {
+                true => addr.to_string(),
+                false => format!("{addr}:4318"),
+            };
+            let path = path.trim_end_matches('/').to_string();
+            Transport::Http { addr, path }
+        }
+    };
+    *OUTPUT.lock().unwrap() = Some(Output {
+        transport,
+        resource: resource(),
+        pending: Pending::default(),
+    });
+    let interval = std::time::Duration::from_millis(interval_ms);
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticks.tick().await;
+            export().await;
+        }
+    });
+    Ok(())
+}
+
+// As a log record, and into its metrics
+pub fn write<Value: crate::json::Fields>(tool: &str, event: &flaregun::Event<Value>) {
+    let mut output = OUTPUT.lock().unwrap();
+    let Some(output) = output.as_mut() else {
+        return;
+    };
+    let pending = &mut output.pending;
+    let time = crate::start_time().timestamp_micros() as u64 * 1000 + event.time.as_nanos() as u64;
+    let frames = |frames: &[flaregun::Frame]| frames.iter().map(|f| f.to_string()).collect();
+    let json = crate::json::Event {
+        tool,
+        time: crate::json::Time::Usecs(event.time.as_micros() as u64),
+        task: crate::bytes_to_str(&event.task),
+        pid: event.pid,
+        fields: event.value.fields(),
+        kernel_stack: event.stack.as_ref().map(|stack| frames(&stack.kernel)),
+        user_stack: event.stack.as_ref().map(|stack| frames(&stack.user)),
+    };
+    if let Ok(serde_json::Value::Object(mut values)) = serde_json::to_value(&json) {
+        // As the record's own
+        values.remove("time");
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        pending.logs.push(LogRecord {
+            time_unix_nano: time,
+            observed_time_unix_nano: now.as_nanos() as u64,
+            body: Some(string(tool)),
+            attributes: key_values(&values),
+            event_name: tool.to_string(),
+            ..Default::default()
+        });
+    }
+    let Some(point) = crate::metrics::Point::of(event) else {
+        return;
+    };
+    for (field, value) in &point.fields {
+        let Some(value) = value.as_f64() else {
+            continue;
+        };
+        let name = match field.as_str() {
+            "value" => format!("flaregun.{tool}"),
+            field => format!("flaregun.{tool}.{field}"),
+        };
+        if crate::metrics::is_latency(tool, field) {
+            // Of what (e.g. a disk), but not of whom, as there'd be too many
+            let tags = point
+                .tags
+                .iter()
+                .filter(|(tag, _)| tag != "task" && tag != "pid")
+                .cloned()
+                .collect();
+            let hist = pending.hists.entry((name, tags));
+            hist.or_insert_with(|| Hist::new(time)).add(time, value);
+        } else if !tool.ends_with("_lat") {
+            let count = crate::metrics::is_count(tool, field);
+            let numbers = match count {
+                true => &mut pending.sums,
+                false => &mut pending.gauges,
+            };
+            let number = numbers.entry((name, point.tags.clone())).or_insert(Number {
+                start: time,
+                time,
+                value: 0.0,
+            });
+            number.time = time;
+            number.value = match count {
+                true => number.value + value,
+                false => value,
+            };
+        }
+        // Otherwise, like a request's sector, it's only in the log record
+    }
+}
+
+fn unit(name: &str) -> &'static str {
+    match name {
+        "flaregun.cpu_pct" | "flaregun.mem_pct" => "%",
+        name if name.ends_with("_lat") || name.ends_with("_us") => "us",
+        _ => "",
+    }
+}
+
+// Data points, by metric
+fn by_metric<Aggregate, DataPoint>(
+    aggregates: std::collections::BTreeMap<Key, Aggregate>,
+    data_point: impl Fn(Aggregate, &[(String, String)]) -> DataPoint,
+) -> std::collections::BTreeMap<String, Vec<DataPoint>> {
+    let mut metrics = std::collections::BTreeMap::<_, Vec<_>>::new();
+    for ((name, tags), aggregate) in aggregates {
+        let data_points = metrics.entry(name).or_default();
+        data_points.push(data_point(aggregate, &tags));
+    }
+    metrics
+}
+
+fn metrics(pending: Pending) -> Vec<Metric> {
+    let delta = AggregationTemporality::Delta as i32;
+    let metric = |name: String, data| Metric {
+        unit: unit(&name).to_string(),
+        name,
+        data: Some(data),
+        ..Default::default()
+    };
+    let mut metrics = Vec::new();
+    for (name, data_points) in by_metric(pending.hists, Hist::data_point) {
+        let data = metric::Data::Histogram(Histogram {
+            data_points,
+            aggregation_temporality: delta,
+        });
+        metrics.push(metric(name, data));
+    }
+    for (name, data_points) in by_metric(pending.gauges, Number::data_point) {
+        metrics.push(metric(name, metric::Data::Gauge(Gauge { data_points })));
+    }
+    for (name, data_points) in by_metric(pending.sums, Number::data_point) {
+        let data = metric::Data::Sum(Sum {
+            data_points,
+            aggregation_temporality: delta,
+            is_monotonic: true,
+        });
+        metrics.push(metric(name, data));
+    }
+    metrics
+}
+
+// A request, without a client, for a local collector
+async fn post(addr: &str, path: &str, body: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+    let head = format!(
+        "POST {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/x-protobuf\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    match status.split(' ').nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("The collector responded with '{status}'").into()),
+    }
+}
+
+impl Transport {
+    async fn export_metrics(
+        &self,
+        request: ExportMetricsServiceRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let export = async {
+            match self {
+                Self::Grpc(channel) => {
+                    MetricsServiceClient::new(channel.clone())
+                        .export(request)
+                        .await?;
+                }
+                Self::Http { addr, path } => {
+                    post(addr, &format!("{path}/v1/metrics"), request.encode_to_vec()).await?
+                }
+            }
+            Ok(())
+        };
+        tokio::time::timeout(TIMEOUT, export)
+            .await
+            .map_err(|_| "Timed out exporting metrics")?
+    }
+
+    async fn export_logs(
+        &self,
+        request: ExportLogsServiceRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let export = async {
+            match self {
+                Self::Grpc(channel) => {
+                    LogsServiceClient::new(channel.clone())
+                        .export(request)
+                        .await?;
+                }
+                Self::Http { addr, path } => {
+                    post(addr, &format!("{path}/v1/logs"), request.encode_to_vec()).await?
+                }
+            }
+            Ok(())
+        };
+        tokio::time::timeout(TIMEOUT, export)
+            .await
+            .map_err(|_| "Timed out exporting logs")?
+    }
+}
+
+async fn try_export(
+    transport: &Transport,
+    resource: &Resource,
+    mut pending: Pending,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut logs = std::mem::take(&mut pending.logs);
+    let metrics = metrics(pending);
+    if !metrics.is_empty() {
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(resource.clone()),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: scope(),
+                    metrics,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        transport.export_metrics(request).await?;
+    }
+    while !logs.is_empty() {
+        let rest = logs.split_off(logs.len().min(LOG_RECORDS_PER_EXPORT));
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(resource.clone()),
+                scope_logs: vec![ScopeLogs {
+                    scope: scope(),
+                    log_records: std::mem::replace(&mut logs, rest),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        transport.export_logs(request).await?;
+    }
+    Ok(())
+}
+
+async fn export() {
+    let taken = OUTPUT.lock().unwrap().as_mut().map(|output| {
+        let pending = std::mem::take(&mut output.pending);
+        (output.transport.clone(), output.resource.clone(), pending)
+    });
+    let Some((transport, resource, pending)) = taken else {
+        return;
+    };
+    if let Err(e) = try_export(&transport, &resource, pending).await {
+        log::error!("Error exporting to the OTLP collector: {e}");
+    }
+}
+
+// Exports what's left
+pub async fn finish() {
+    export().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A stand-in collector, over HTTP, which sends on each request's path and
+    // body, and responds, unless it's to hang
+    async fn collector(
+        respond: bool,
+    ) -> (
+        String,
+        tokio::sync::mpsc::UnboundedReceiver<(String, Vec<u8>)>,
+    ) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut hung = Vec::new();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                if !respond {
+                    hung.push(stream);
+                    continue;
+                }
+                let mut buf = Vec::new();
+                let end = loop {
+                    let mut chunk = [0; 4096];
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(at) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break at + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buf[..end]).to_string();
+                let len: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let mut body = buf.split_off(end);
+                let read = body.len();
+                body.resize(len, 0);
+                stream.read_exact(&mut body[read..]).await.unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .await
+                    .unwrap();
+                let path = head.split(' ').nth(1).unwrap().to_string();
+                tx.send((path, body)).unwrap();
+            }
+        });
+        (addr, rx)
+    }
+
+    fn event<Value>(pid: u32, value: Value) -> flaregun::Event<Value> {
+        flaregun::Event {
+            time: std::time::Duration::from_millis(1),
+            task: std::array::from_fn(|i| b"task".get(i).copied().unwrap_or(0)),
+            pid,
+            value,
+            stack: None,
+        }
+    }
+
+    fn metric<'a>(request: &'a ExportMetricsServiceRequest, name: &str) -> &'a metric::Data {
+        let metrics = &request.resource_metrics[0].scope_metrics[0].metrics;
+        let metric = metrics.iter().find(|metric| metric.name == name).unwrap();
+        metric.data.as_ref().unwrap()
+    }
+
+    #[tokio::test]
+    async fn exports_to_a_collector() {
+        use prost::Message;
+        let (addr, mut requests) = collector(true).await;
+        let endpoint = format!("http://{addr}/otlp/");
+        init(Protocol::HttpProtobuf, Some(&endpoint), 3_600_000).unwrap();
+        for lat_us in [3u64, 100, 5000] {
+            write("rq_lat", &event(7, lat_us));
+        }
+        write("cpu_pct", &event(7, "12.5".to_string()));
+        let migrations = flaregun::Migrations {
+            count: 2,
+            orig_cpu: 0,
+            dest_cpu: 1,
+        };
+        write("cpu_migrate", &event(7, migrations));
+        write("cpu_migrate", &event(7, migrations));
+        finish().await;
+
+        let (path, body) = requests.recv().await.unwrap();
+        assert_eq!(path, "/otlp/v1/metrics");
+        let request = ExportMetricsServiceRequest::decode(body.as_slice()).unwrap();
+        let resource = request.resource_metrics[0].resource.as_ref().unwrap();
+        assert!(resource
+            .attributes
+            .contains(&key_value("service.name", string("flaregun"))));
+        let delta = AggregationTemporality::Delta as i32;
+        let metric::Data::Histogram(hist) = metric(&request, "flaregun.rq_lat") else {
+            panic!("Expected a histogram of rq_lat");
+        };
+        assert_eq!(hist.aggregation_temporality, delta);
+        let point = &hist.data_points[0];
+        // Of whom isn't an attribute of latencies
+        assert!(point.attributes.is_empty());
+        assert_eq!((point.count, point.sum), (3, Some(5103.0)));
+        // In (2, 4], (64, 128] and (4096, 8192]
+        let mut buckets = vec![0; BOUNDS_US.len() + 1];
+        (buckets[2], buckets[7], buckets[13]) = (1, 1, 1);
+        assert_eq!(point.bucket_counts, buckets);
+        let metric::Data::Gauge(gauge) = metric(&request, "flaregun.cpu_pct") else {
+            panic!("Expected a gauge of cpu_pct");
+        };
+        let point = &gauge.data_points[0];
+        assert_eq!(point.value, Some(number_data_point::Value::AsDouble(12.5)));
+        assert!(point.attributes.contains(&key_value(
+            "pid",
+            AnyValue {
+                value: Some(any_value::Value::IntValue(7)),
+            }
+        )));
+        let metric::Data::Sum(sum) = metric(&request, "flaregun.cpu_migrate") else {
+            panic!("Expected a sum of cpu_migrate");
+        };
+        assert_eq!(sum.aggregation_temporality, delta);
+        assert!(sum.is_monotonic);
+        assert_eq!(
+            sum.data_points[0].value,
+            Some(number_data_point::Value::AsDouble(4.0))
+        );
+
+        let (path, body) = requests.recv().await.unwrap();
+        assert_eq!(path, "/otlp/v1/logs");
+        let request = ExportLogsServiceRequest::decode(body.as_slice()).unwrap();
+        let records = &request.resource_logs[0].scope_logs[0].log_records;
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[0].event_name, "rq_lat");
+        for (key, value) in [
+            ("task", string("task")),
+            ("value", any_value(&3.into()).unwrap()),
+        ] {
+            assert!(records[0].attributes.contains(&key_value(key, value)));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn times_out_when_a_collector_hangs() {
+        let (addr, _) = collector(false).await;
+        let endpoint = tonic::transport::Endpoint::from_shared(format!("http://{addr}")).unwrap();
+        let transports = [
+            Transport::Grpc(endpoint.connect_lazy()),
+            Transport::Http {
+                addr,
+                path: String::new(),
+            },
+        ];
+        for transport in transports {
+            let export = transport.export_logs(ExportLogsServiceRequest::default());
+            assert!(export.await.is_err());
+        }
+    }
+}
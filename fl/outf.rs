@@ -1,18 +1,88 @@
-static OUTF: std::sync::Mutex<Option<std::fs::File>> = std::sync::Mutex::new(None);
+// A local endpoint, like "udp://127.0.0.1:8094" or "tcp://localhost:8094", to
+// write to instead of a file
+#[derive(Debug, Clone)]
+pub enum Endpoint {
+    Udp(String),
+    Tcp(String),
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once("://") {
+            Some(("udp", addr)) => Ok(Self::Udp(addr.to_string())),
+            Some(("tcp", addr)) => Ok(Self::Tcp(addr.to_string())),
+            _ => Err(format!(
+                "Expected udp://<host>:<port> or tcp://<host>:<port>, got '{s}'"
+            )),
+        }
+    }
+}
+
+// Lines are sent in datagrams of up to this many bytes (or of one line, if it's
+// longer), to fit in a packet
+const DATAGRAM_BYTES: usize = 1400;
+
+enum Out {
+    File(std::fs::File),
+    Udp(std::net::UdpSocket),
+    Tcp(std::net::TcpStream),
+}
+
+impl Out {
+    fn write_line(&self, s: &str) -> Result<(), std::io::Error> {
+        use std::io::Write;
+        match self {
+            Self::File(file) => writeln!(&mut &*file, "{s}"),
+            Self::Tcp(stream) => writeln!(&mut &*stream, "{s}"),
+            Self::Udp(socket) => {
+                let mut datagram = String::new();
+                for line in s.lines().filter(|line| !line.is_empty()) {
+                    if !datagram.is_empty() && datagram.len() + line.len() + 1 > DATAGRAM_BYTES {
+                        socket.send(datagram.as_bytes())?;
+                        datagram.clear();
+                    }
+                    datagram.push_str(line);
+                    datagram.push('\n');
+                }
+                if !datagram.is_empty() {
+                    socket.send(datagram.as_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+static OUTF: std::sync::Mutex<Option<Out>> = std::sync::Mutex::new(None);
 
 fn try_init_nonuniq(path: &std::path::PathBuf) -> Result<(), std::io::Error> {
     let mut file = OUTF.lock().unwrap();
     if file.is_none() {
-        *file = Some(
+        *file = Some(Out::File(
             std::fs::OpenOptions::new()
                 .create(true)
                 .append(true)
                 .open(path)?,
-        );
+        ));
     }
     Ok(())
 }
 
+// Writes to an endpoint instead, after `init()` without a file
+pub fn connect(endpoint: &Endpoint) -> Result<(), std::io::Error> {
+    let out = match endpoint {
+        Endpoint::Udp(addr) => {
+            let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+            socket.connect(addr)?;
+            Out::Udp(socket)
+        }
+        Endpoint::Tcp(addr) => Out::Tcp(std::net::TcpStream::connect(addr)?),
+    };
+    *OUTF.lock().unwrap() = Some(out);
+    Ok(())
+}
+
 pub fn init(path: &Option<std::path::PathBuf>) {
     static CALLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
     if CALLED.swap(true, std::sync::atomic::Ordering::SeqCst) {
@@ -23,10 +93,9 @@ pub fn init(path: &Option<std::path::PathBuf>) {
 }
 
 pub fn try_write_line(s: &str) -> Result<(), std::io::Error> {
-    use std::io::Write;
     match OUTF.lock() {
-        Ok(file) => match file.as_ref() {
-            Some(mut file) => writeln!(file, "{s}"),
+        Ok(out) => match out.as_ref() {
+            Some(out) => out.write_line(s),
             None => Ok(println!("{s}")),
         },
         Err(_) => Err(std::io::Error::new(
@@ -104,7 +173,8 @@ pub fn buf_flush() {
 }
 
 // A `println!`-compatible macro that
-// - Writes to the output file specified in a previous call to `init()`
+// - Writes to the output file specified in a previous call to `init()`, or to
+//   the endpoint of `connect()`
 // - Writes to stdout, if no output file was specified
 // - Writes to stdout, if any errors occur while writing to the output file
 // - Writes to stdout and `log::error!`s on any other errors
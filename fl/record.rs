@@ -0,0 +1,537 @@
+// Events in a binary recording, as `fl record` writes them, and as `fl replay`
+// reads them back into any output format. Smaller, and cheaper to write, than
+// any of the text formats.
+//
+// A recording starts with a magic number and a version, and then has chunks,
+// each a length (u32, of what follows), a kind (u8) and its content:
+// - A header, as json, with the kernel's version, and when it booted and when
+//   `fl` started (in microseconds since the epoch)
+// - A tool, before any of its events, as its id (u8), then json with its name
+//   and `Cfg`
+// - An event, as its tool's id, time (in microseconds since `fl` started),
+//   task, pid, value (as laid out for its type below) and stack, if any
+// Numbers are little endian, and strings are prefixed with their length (u32).
+// Chunks of unknown kinds are skipped, so that they may be added later.
+
+const MAGIC: &[u8; 4] = b"FLR\0";
+const VERSION: u16 = 2;
+
+const HEADER: u8 = 0;
+const TOOL: u8 = 1;
+const EVENT: u8 = 2;
+
+// Far more than any chunk takes, so that a corrupt length isn't allocated
+const MAX_CHUNK_LEN: u64 = 16 << 20;
+
+pub trait Encode: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+    // Takes what it reads from the front of `buf`, or None if it's too short
+    fn decode(buf: &mut &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_encode_for_numbers {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+            fn decode(buf: &mut &[u8]) -> Option<Self> {
+                let (bytes, rest) = buf.split_first_chunk()?;
+                *buf = rest;
+                Some(Self::from_le_bytes(*bytes))
+            }
+        }
+    )*};
+}
+
+impl_encode_for_numbers!(u8, u16, u32, u64, i64, f32);
+
+impl Encode for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u32).encode(buf);
+        buf.extend_from_slice(self.as_bytes());
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        let len = u32::decode(buf)? as usize;
+        let (bytes, rest) = buf.split_at_checked(len)?;
+        *buf = rest;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+// As its version (4 or 6), then its octets
+impl Encode for std::net::IpAddr {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            std::net::IpAddr::V4(addr) => {
+                buf.push(4);
+                buf.extend_from_slice(&addr.octets());
+            }
+            std::net::IpAddr::V6(addr) => {
+                buf.push(6);
+                buf.extend_from_slice(&addr.octets());
+            }
+        }
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        match u8::decode(buf)? {
+            4 => {
+                let (octets, rest) = buf.split_first_chunk::<4>()?;
+                *buf = rest;
+                Some(std::net::Ipv4Addr::from(*octets).into())
+            }
+            6 => {
+                let (octets, rest) = buf.split_first_chunk::<16>()?;
+                *buf = rest;
+                Some(std::net::Ipv6Addr::from(*octets).into())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Some(t) => {
+                buf.push(1);
+                t.encode(buf);
+            }
+            None => buf.push(0),
+        }
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        match u8::decode(buf)? {
+            0 => Some(None),
+            _ => Some(Some(T::decode(buf)?)),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (self.len() as u32).encode(buf);
+        for t in self {
+            t.encode(buf);
+        }
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        let len = u32::decode(buf)?;
+        (0..len).map(|_| T::decode(buf)).collect()
+    }
+}
+
+// Encodes and decodes each of a struct's fields, in order
+macro_rules! impl_encode_for_struct {
+    ($t:ty { $($field:ident),* }) => {
+        impl Encode for $t {
+            fn encode(&self, buf: &mut Vec<u8>) {
+                $(self.$field.encode(buf);)*
+            }
+            fn decode(buf: &mut &[u8]) -> Option<Self> {
+                Some(Self {
+                    $($field: Encode::decode(buf)?,)*
+                })
+            }
+        }
+    };
+}
+
+impl_encode_for_struct!(flaregun::Frame {
+    addr,
+    symbol,
+    offset,
+    object
+});
+impl_encode_for_struct!(flaregun::Stack { kernel, user });
+impl_encode_for_struct!(flaregun::QueueLen { cpu, avg, max });
+impl_encode_for_struct!(flaregun::Migrations {
+    count,
+    orig_cpu,
+    dest_cpu
+});
+impl_encode_for_struct!(flaregun::DiskStat {
+    dev,
+    iops,
+    bytes,
+    in_flight,
+    reads,
+    writes,
+    discards,
+    flushes
+});
+impl_encode_for_struct!(flaregun::FuncCall { func, lat_us });
+impl_encode_for_struct!(flaregun::TcpPkt {
+    lat_us,
+    saddr,
+    daddr,
+    sport,
+    dport
+});
+
+impl Encode for flaregun::FuncHist {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.func.encode(buf);
+        self.slots.to_vec().encode(buf);
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        let func = String::decode(buf)?;
+        let slots = Vec::<u64>::decode(buf)?;
+        Some(Self {
+            func,
+            slots: slots.try_into().ok()?,
+        })
+    }
+}
+
+impl Encode for flaregun::Bio {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.lat_us.encode(buf);
+        self.q_lat_us.encode(buf);
+        self.sector.encode(buf);
+        self.len.encode(buf);
+        self.dev.encode(buf);
+        u8::from(self.op).encode(buf);
+        u32::from(self.flags).encode(buf);
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        Some(Self {
+            lat_us: u64::decode(buf)?,
+            q_lat_us: u64::decode(buf)?,
+            sector: u64::decode(buf)?,
+            len: u32::decode(buf)?,
+            dev: u32::decode(buf)?,
+            op: flaregun::BioOp::from(u8::decode(buf)? as u32),
+            flags: flaregun::BioFlags::from(u32::decode(buf)?),
+        })
+    }
+}
+
+impl Encode for flaregun::FsCall {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.lat_us.encode(buf);
+        self.file.encode(buf);
+        u8::from(self.op).encode(buf);
+        self.size.encode(buf);
+        self.offset.encode(buf);
+    }
+    fn decode(buf: &mut &[u8]) -> Option<Self> {
+        Some(Self {
+            lat_us: u64::decode(buf)?,
+            file: String::decode(buf)?,
+            op: flaregun::FsOp::from(u8::decode(buf)?),
+            size: i64::decode(buf)?,
+            offset: i64::decode(buf)?,
+        })
+    }
+}
+
+fn encode_event<Value: Encode>(tool: u8, event: &flaregun::Event<Value>) -> Vec<u8> {
+    let mut buf = vec![tool];
+    (event.time.as_micros() as u64).encode(&mut buf);
+    crate::bytes_to_str(&event.task)
+        .to_string()
+        .encode(&mut buf);
+    event.pid.encode(&mut buf);
+    event.value.encode(&mut buf);
+    event.stack.encode(&mut buf);
+    buf
+}
+
+// After the tool's id
+fn decode_event<Value: Encode>(buf: &mut &[u8]) -> Option<flaregun::Event<Value>> {
+    let time = std::time::Duration::from_micros(u64::decode(buf)?);
+    let task = String::decode(buf)?;
+    // Nul-padded, like the kernel's
+    let task = std::array::from_fn(|i| task.as_bytes().get(i).copied().unwrap_or(0));
+    Some(flaregun::Event {
+        time,
+        task,
+        pid: u32::decode(buf)?,
+        value: Value::decode(buf)?,
+        stack: Option::decode(buf)?,
+    })
+}
+
+struct Recording {
+    file: std::io::BufWriter<std::fs::File>,
+    tools: u8,
+}
+
+impl Recording {
+    fn write(&mut self, kind: u8, content: &[u8]) -> Result<(), std::io::Error> {
+        use std::io::Write;
+        self.file
+            .write_all(&(content.len() as u32 + 1).to_le_bytes())?;
+        self.file.write_all(&[kind])?;
+        self.file.write_all(content)
+    }
+}
+
+static RECORDING: std::sync::Mutex<Option<Recording>> = std::sync::Mutex::new(None);
+
+pub fn kernel_release() -> String {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    match unsafe { libc::uname(&mut uts) } {
+        0 => unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) }
+            .to_string_lossy()
+            .to_string(),
+        _ => "?".to_string(),
+    }
+}
+
+// In microseconds since the epoch, when the kernel's monotonic clock started,
+// so that events' kernel timestamps are their times plus (start_time_us -
+// boot_time_us)
+fn boot_time_us() -> i64 {
+    let boot_time = flaregun::time::boot_time().duration_since(std::time::UNIX_EPOCH);
+    boot_time.unwrap_or_default().as_micros() as i64
+}
+
+// Truncates the file, and writes the header
+pub fn init(path: &std::path::Path) -> Result<(), std::io::Error> {
+    use std::io::Write;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    let header = serde_json::json!({
+        "fl_version": env!("CARGO_PKG_VERSION"),
+        "kernel": kernel_release(),
+        "boot_time_us": boot_time_us(),
+        "start_time_us": crate::start_time().timestamp_micros(),
+    });
+    let mut recording = Recording { file, tools: 0 };
+    recording.write(HEADER, header.to_string().as_bytes())?;
+    *RECORDING.lock().unwrap() = Some(recording);
+    Ok(())
+}
+
+// Before any of its events, for the id they're recorded with
+pub fn tool(name: &str, cfg: &flaregun::Cfg) -> u8 {
+    let json = serde_json::json!({
+        "name": name,
+        "cfg": {
+            "min_lat_us": cfg.min_lat_us,
+            "targ_reporting_interval_ms": cfg.targ_reporting_interval_ms,
+            "targ_pid": cfg.targ_pid,
+            "targ_tgid": cfg.targ_tgid,
+            "targ_dev": cfg.targ_dev,
+            "targ_filter_dev": cfg.targ_filter_dev,
+            "targ_filter_cgroup": cfg.targ_filter_cgroup,
+            "targ_filter_queued": cfg.targ_filter_queued,
+            "targ_bio_op": cfg.targ_bio_op.map(|op| op.to_string()),
+            "targ_uprobe": cfg.targ_uprobe.as_ref().map(|uprobe| uprobe.to_string()),
+            "targ_func": cfg.targ_func,
+            "targ_stacks": cfg.targ_stacks,
+            "targ_sample_freq_hz": cfg.targ_sample_freq_hz,
+        },
+    });
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(recording) = recording.as_mut() else {
+        return 0;
+    };
+    let id = recording.tools;
+    recording.tools += 1;
+    let mut content = vec![id];
+    content.extend_from_slice(json.to_string().as_bytes());
+    if let Err(e) = recording.write(TOOL, &content) {
+        log::error!("{e:?}");
+    }
+    id
+}
+
+pub fn event<Value: Encode>(tool: u8, event: &flaregun::Event<Value>) {
+    let content = encode_event(tool, event);
+    if let Some(recording) = RECORDING.lock().unwrap().as_mut() {
+        if let Err(e) = recording.write(EVENT, &content) {
+            log::error!("{e:?}");
+        }
+    }
+}
+
+pub fn flush() {
+    use std::io::Write;
+    if let Some(recording) = RECORDING.lock().unwrap().as_mut() {
+        if let Err(e) = recording.file.flush() {
+            log::error!("{e:?}");
+        }
+    }
+}
+
+// Each chunk's kind and content, of some kinds (skipping the others), read one
+// at a time. A recording which was cut short (e.g. if `fl` was killed) loses
+// only its last chunk.
+struct Chunks {
+    file: std::io::BufReader<std::fs::File>,
+    kinds: &'static [u8],
+    cut_short: bool,
+}
+
+impl Chunks {
+    // After the magic number and version
+    fn open(
+        path: &std::path::Path,
+        kinds: &'static [u8],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        use std::io::Read;
+        let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut start = [0u8; MAGIC.len() + 2];
+        let version = match file.read_exact(&mut start) {
+            Ok(()) => start
+                .strip_prefix(MAGIC)
+                .and_then(|mut buf| u16::decode(&mut buf)),
+            Err(_) => None,
+        };
+        match version {
+            Some(VERSION) => (),
+            Some(version) => return Err(format!("Unsupported recording version: {version}").into()),
+            None => return Err("Not a recording (from 'fl record')".into()),
+        }
+        Ok(Self {
+            file,
+            kinds,
+            cut_short: false,
+        })
+    }
+
+    // None if it's of a kind to skip
+    fn read(&mut self) -> Result<Option<(u8, Vec<u8>)>, std::io::Error> {
+        use std::io::Read;
+        let mut len = [0u8; 4];
+        self.file.read_exact(&mut len)?;
+        let Some(len) = (u32::from_le_bytes(len) as u64).checked_sub(1) else {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        };
+        if len > MAX_CHUNK_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("A chunk of {len} bytes is too long"),
+            ));
+        }
+        let mut kind = [0u8];
+        self.file.read_exact(&mut kind)?;
+        if !self.kinds.contains(&kind[0]) {
+            let skipped = std::io::copy(&mut (&mut self.file).take(len), &mut std::io::sink())?;
+            return match skipped == len {
+                true => Ok(None),
+                false => Err(std::io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        let mut content = vec![0; len as usize];
+        self.file.read_exact(&mut content)?;
+        Ok(Some((kind[0], content)))
+    }
+}
+
+impl Iterator for Chunks {
+    type Item = (u8, Vec<u8>);
+    fn next(&mut self) -> Option<Self::Item> {
+        use std::io::BufRead;
+        loop {
+            match self.file.fill_buf() {
+                Ok([]) => return None,
+                Ok(_) => (),
+                Err(e) => {
+                    log::error!("{e:?}");
+                    return None;
+                }
+            }
+            match self.read() {
+                Ok(Some(chunk)) => return Some(chunk),
+                Ok(None) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    log::error!("Corrupt recording: {e}");
+                    return None;
+                }
+                Err(_) => {
+                    self.cut_short = true;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+fn json(content: &[u8]) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_slice(content)?)
+}
+
+// Streamed, in two passes: the first for the header and tools, which the
+// output's columns depend on, and the second for the events
+pub fn replay(opts: &crate::Cli, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tools = std::collections::HashMap::new();
+    for (kind, content) in Chunks::open(path, &[HEADER, TOOL])? {
+        match kind {
+            HEADER => {
+                let header = json(&content)?;
+                log::info!("Recording: {header}");
+                if let Some(us) = header["start_time_us"].as_i64() {
+                    crate::set_start_time(us);
+                }
+            }
+            TOOL => {
+                let (id, content) = content.split_first().ok_or("Empty tool in recording")?;
+                match json(content)?["name"].as_str() {
+                    Some(name) => tools.insert(*id, name.to_string()),
+                    None => return Err("Unnamed tool in recording".into()),
+                };
+            }
+            _ => (),
+        }
+    }
+    let mut names: Vec<(u8, &str)> = tools
+        .iter()
+        .map(|(id, name)| (*id, name.as_str()))
+        .collect();
+    names.sort();
+    let names: Vec<&str> = names.into_iter().map(|(_, name)| name).collect();
+    let columns = crate::tool_columns(&names);
+    if !opts.no_header {
+        crate::show_header(opts, &columns);
+    }
+    if opts.just_header {
+        return Ok(());
+    }
+    let time_format = opts.duration_format.unwrap_or(opts.time_format);
+    let mut chunks = Chunks::open(path, &[EVENT])?;
+    for (_, content) in chunks.by_ref() {
+        let mut content = content.as_slice();
+        let Some(tool) = u8::decode(&mut content).and_then(|id| tools.get(&id)) else {
+            log::error!("Event of an unknown tool in recording");
+            continue;
+        };
+        macro_rules! show {
+            ($Value:ty) => {
+                match decode_event::<$Value>(&mut content) {
+                    Some(event) => crate::show_event(
+                        tool,
+                        opts.output_format,
+                        time_format,
+                        opts.buffered,
+                        &columns,
+                        &event,
+                    ),
+                    None => log::error!("Error decoding an event of {tool} in recording"),
+                }
+            };
+        }
+        match tool.as_str() {
+            "bio_lat" => show!(flaregun::Bio),
+            "fs_lat" => show!(flaregun::FsCall),
+            "tcp_pkt_lat" => show!(flaregun::TcpPkt),
+            "rq_lat" | "profile" | "uprobe_lat" => show!(u64),
+            "cpu_pct" | "mem_pct" => show!(String),
+            "runq_len" => show!(flaregun::QueueLen),
+            "cpu_migrate" => show!(flaregun::Migrations),
+            "bio_stat" => show!(flaregun::DiskStat),
+            "func_lat" => show!(flaregun::FuncCall),
+            "func_lat_hist" => show!(flaregun::FuncHist),
+            tool => log::error!("Event of an unknown tool in recording: {tool}"),
+        }
+    }
+    if chunks.cut_short {
+        log::warn!("Recording was cut short");
+    }
+    crate::outf::buf_flush();
+    Ok(())
+}
@@ -0,0 +1,369 @@
+// A live, full-screen view of what the tools are seeing, for when every event
+// on its own line scrolls by too fast to read. Events are recorded from the
+// tools' tasks, and drawn every reporting interval.
+
+// How far back rates and percentiles are taken over
+const WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
+// Samples of cpu and memory utilization kept for their sparklines
+const SPARKLINE_LEN: usize = 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Total,
+    Count,
+    Max,
+    Pid,
+    Comm,
+}
+
+impl SortBy {
+    fn next(self) -> Self {
+        match self {
+            SortBy::Total => SortBy::Count,
+            SortBy::Count => SortBy::Max,
+            SortBy::Max => SortBy::Pid,
+            SortBy::Pid => SortBy::Comm,
+            SortBy::Comm => SortBy::Total,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortBy::Total => "total latency",
+            SortBy::Count => "events",
+            SortBy::Max => "max latency",
+            SortBy::Pid => "pid",
+            SortBy::Comm => "comm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Pid(u32),
+    // A substring of the task's name
+    Comm(String),
+}
+
+impl Filter {
+    fn matches(&self, task: &str, pid: u32) -> bool {
+        match self {
+            Filter::Pid(p) => *p == pid,
+            Filter::Comm(comm) => task.contains(comm.as_str()),
+        }
+    }
+}
+
+// A pid or comm filter, as it's typed
+enum Prompt {
+    Pid(String),
+    Comm(String),
+}
+
+struct Sample {
+    at: std::time::Instant,
+    value: f64,
+    task: String,
+    pid: u32,
+}
+
+// Cumulative latency of a task, from every latency tool
+#[derive(Default)]
+struct TaskStats {
+    count: u64,
+    total_us: f64,
+    max_us: f64,
+}
+
+#[derive(Default)]
+struct State {
+    samples: std::collections::BTreeMap<&'static str, std::collections::VecDeque<Sample>>,
+    tasks: std::collections::HashMap<(String, u32), TaskStats>,
+    cpu_pct: std::collections::VecDeque<f64>,
+    mem_pct: std::collections::VecDeque<f64>,
+}
+
+static STATE: std::sync::Mutex<Option<State>> = std::sync::Mutex::new(None);
+
+fn is_latency(tool: &str) -> bool {
+    tool.ends_with("_lat")
+}
+
+pub fn record<Value: std::fmt::Display>(tool: &'static str, event: &flaregun::Event<Value>) {
+    // The values are all numbers, some of them (like cpu_pct's) already formatted
+    let Ok(value) = event.value.to_string().parse::<f64>() else {
+        return;
+    };
+    let task = crate::bytes_to_str(&event.task).to_string();
+    let mut state = STATE.lock().unwrap();
+    let state = state.get_or_insert_with(State::default);
+    let sparkline = match tool {
+        "cpu_pct" => Some(&mut state.cpu_pct),
+        "mem_pct" => Some(&mut state.mem_pct),
+        _ => None,
+    };
+    if let Some(sparkline) = sparkline {
+        if sparkline.len() == SPARKLINE_LEN {
+            sparkline.pop_front();
+        }
+        sparkline.push_back(value);
+        return;
+    }
+    if is_latency(tool) {
+        let stats = state.tasks.entry((task.clone(), event.pid)).or_default();
+        stats.count += 1;
+        stats.total_us += value;
+        stats.max_us = stats.max_us.max(value);
+    }
+    state.samples.entry(tool).or_default().push_back(Sample {
+        at: std::time::Instant::now(),
+        value,
+        task,
+        pid: event.pid,
+    });
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        n => sorted[((p / 100.0 * n as f64).ceil() as usize).clamp(1, n) - 1],
+    }
+}
+
+fn sparkline(values: &std::collections::VecDeque<f64>) -> String {
+    values
+        .iter()
+        .map(|v| SPARKS[((v / 100.0 * SPARKS.len() as f64) as usize).min(SPARKS.len() - 1)])
+        .collect()
+}
+
+// Rows and columns, or a guess if standard output isn't a terminal
+fn terminal_size() -> (usize, usize) {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } {
+        0 if ws.ws_row > 0 && ws.ws_col > 0 => (ws.ws_row as usize, ws.ws_col as usize),
+        _ => (24, 80),
+    }
+}
+
+struct View {
+    interval_ms: u64,
+    sort_by: SortBy,
+    filter: Option<Filter>,
+    prompt: Option<Prompt>,
+}
+
+impl View {
+    fn draw(&self) -> String {
+        let (rows, cols) = terminal_size();
+        let mut lines = Vec::new();
+        let filter = match (&self.prompt, &self.filter) {
+            (Some(Prompt::Pid(pid)), _) => format!("pid: {pid}_"),
+            (Some(Prompt::Comm(comm)), _) => format!("comm: {comm}_"),
+            (None, Some(Filter::Pid(pid))) => format!("pid {pid}"),
+            (None, Some(Filter::Comm(comm))) => format!("comm ~ {comm}"),
+            (None, None) => "none".to_string(),
+        };
+        lines.push(format!(
+            "fl top, every {}ms | sort: {} | filter: {filter}",
+            self.interval_ms,
+            self.sort_by.name()
+        ));
+        lines.push("s: sort, p: pid, c: comm, esc: clear, q: quit".to_string());
+        lines.push(String::new());
+        let mut state = STATE.lock().unwrap();
+        let state = state.get_or_insert_with(State::default);
+        for (name, values) in [("cpu %", &state.cpu_pct), ("mem %", &state.mem_pct)] {
+            if let Some(last) = values.back() {
+                lines.push(format!("{name} {:>6.2} {}", last, sparkline(values)));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<14} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "tool", "events/s", "p50", "p90", "p99", "p99.9", "max"
+        ));
+        let now = std::time::Instant::now();
+        for (tool, samples) in state.samples.iter_mut() {
+            while samples.front().is_some_and(|s| now - s.at > WINDOW) {
+                samples.pop_front();
+            }
+            let mut values: Vec<f64> = samples
+                .iter()
+                .filter(|s| {
+                    self.filter
+                        .as_ref()
+                        .is_none_or(|f| f.matches(&s.task, s.pid))
+                })
+                .map(|s| s.value)
+                .collect();
+            values.sort_by(f64::total_cmp);
+            let rate = values.len() as f64 / WINDOW.as_secs_f64();
+            let p = |p| percentile(&values, p);
+            lines.push(format!(
+                "{tool:<14} {rate:>10.1} {:>10.0} {:>10.0} {:>10.0} {:>10.0} {:>10.0}",
+                p(50.0),
+                p(90.0),
+                p(99.0),
+                p(99.9),
+                p(100.0)
+            ));
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<20} {:>8} {:>10} {:>14} {:>12}",
+            "task", "pid", "events", "total ms", "max us"
+        ));
+        let mut tasks: Vec<_> = state
+            .tasks
+            .iter()
+            .filter(|((task, pid), _)| self.filter.as_ref().is_none_or(|f| f.matches(task, *pid)))
+            .collect();
+        match self.sort_by {
+            SortBy::Total => tasks.sort_by(|a, b| b.1.total_us.total_cmp(&a.1.total_us)),
+            SortBy::Count => tasks.sort_by_key(|task| std::cmp::Reverse(task.1.count)),
+            SortBy::Max => tasks.sort_by(|a, b| b.1.max_us.total_cmp(&a.1.max_us)),
+            SortBy::Pid => tasks.sort_by_key(|((_, pid), _)| *pid),
+            SortBy::Comm => tasks.sort_by(|a, b| a.0 .0.cmp(&b.0 .0)),
+        }
+        for ((task, pid), stats) in tasks.into_iter().take(rows.saturating_sub(lines.len())) {
+            lines.push(format!(
+                "{task:<20} {pid:>8} {:>10} {:>14.1} {:>12.0}",
+                stats.count,
+                stats.total_us / 1000.0,
+                stats.max_us
+            ));
+        }
+        let lines: Vec<String> = lines
+            .into_iter()
+            .take(rows)
+            .map(|line| line.chars().take(cols).collect())
+            .collect();
+        // Home, then each line cleared to its end, then the rest of the screen
+        format!("\x1b[H{}\x1b[J", lines.join("\x1b[K\r\n") + "\x1b[K")
+    }
+
+    // False to quit
+    fn key(&mut self, key: u8) -> bool {
+        const CTRL_C: u8 = 3;
+        const ESC: u8 = 27;
+        if let Some(prompt) = &mut self.prompt {
+            match (prompt, key) {
+                (_, ESC) => self.prompt = None,
+                (Prompt::Pid(pid), b'\r' | b'\n') => {
+                    self.filter = pid.parse().ok().map(Filter::Pid);
+                    self.prompt = None;
+                }
+                (Prompt::Comm(comm), b'\r' | b'\n') => {
+                    self.filter = Some(Filter::Comm(std::mem::take(comm)));
+                    self.prompt = None;
+                }
+                (Prompt::Pid(s) | Prompt::Comm(s), 127 | 8) => {
+                    s.pop();
+                }
+                (Prompt::Pid(pid), b'0'..=b'9') => pid.push(key as char),
+                (Prompt::Comm(comm), b' '..=b'~') => comm.push(key as char),
+                _ => (),
+            }
+            return key != CTRL_C;
+        }
+        match key {
+            b'q' | CTRL_C => return false,
+            b's' => self.sort_by = self.sort_by.next(),
+            b'p' => self.prompt = Some(Prompt::Pid(String::new())),
+            b'c' => self.prompt = Some(Prompt::Comm(String::new())),
+            ESC => self.filter = None,
+            _ => (),
+        }
+        true
+    }
+}
+
+// Keys unbuffered and unechoed, on the alternate screen, until dropped
+struct Terminal {
+    termios: Option<libc::termios>,
+}
+
+impl Terminal {
+    fn enter() -> Self {
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        let termios = match unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } {
+            0 => {
+                let mut raw = termios;
+                // Without ISIG, a ctrl-c is a key, so that we get to restore the terminal
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+                Some(termios)
+            }
+            _ => None,
+        };
+        print!("\x1b[?1049h\x1b[?25l");
+        Self { termios }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        use std::io::Write;
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        if let Some(termios) = &self.termios {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios) };
+        }
+    }
+}
+
+// Keys are read on their own thread, as a read of standard input can't be
+// cancelled, and tokio's would hold up the runtime's shutdown until a key is hit
+fn read_keys() -> tokio::sync::mpsc::UnboundedReceiver<std::io::Result<u8>> {
+    use std::io::Read;
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin().lock();
+        let mut key = [0u8; 1];
+        loop {
+            let key = match stdin.read(&mut key) {
+                Ok(0) => return,
+                Ok(_) => Ok(key[0]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            if tx.send(key).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+// Until a 'q' (or ctrl-c)
+pub async fn run(interval_ms: u64) -> Result<(), std::io::Error> {
+    use std::io::Write;
+    let _terminal = Terminal::enter();
+    let mut view = View {
+        interval_ms,
+        sort_by: SortBy::Total,
+        filter: None,
+        prompt: None,
+    };
+    let mut keys = read_keys();
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(interval_ms.max(1)));
+    // Standard input may not be a terminal, and closed, in which case there are no keys
+    let mut more_keys = true;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            key = keys.recv(), if more_keys => match key.transpose()? {
+                None => more_keys = false,
+                Some(key) if !view.key(key) => return Ok(()),
+                Some(_) => (),
+            },
+        }
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(view.draw().as_bytes())?;
+        stdout.flush()?;
+    }
+}
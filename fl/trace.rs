@@ -0,0 +1,120 @@
+// Events in the Chrome Trace Event format, which Perfetto (ui.perfetto.dev) and
+// chrome://tracing open, to see what happened at once on one timeline.
+// Latencies are async slices, which end when they're reported, under their
+// task's process, on a track for each tool (as they may overlap, which a
+// thread's slices can't), and the monitors' values are counters. In the "JSON
+// Array Format", with an event on each line, and without the closing bracket,
+// as that's optional, so that a trace cut short by a SIGINT is still whole.
+
+// Async slices' ids, for their begin and end to be matched
+static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+static NAMED: std::sync::Mutex<Option<std::collections::HashSet<u32>>> =
+    std::sync::Mutex::new(None);
+
+// Names the track of a pid (as a process, with a thread), the first time it's
+// seen, by its task
+fn name(pid: u32, task: &str) -> Option<String> {
+    let mut named = NAMED.lock().unwrap();
+    if !named.get_or_insert_with(Default::default).insert(pid) {
+        return None;
+    }
+    let meta = |name| {
+        serde_json::json!({
+            "ph": "M",
+            "name": name,
+            "pid": pid,
+            "tid": pid,
+            "args": { "name": task },
+        })
+    };
+    Some(format!(
+        "{},\n{},",
+        meta("process_name"),
+        meta("thread_name")
+    ))
+}
+
+// Counters of one cpu, disk or function are on their own track
+fn counter_name(tool: &str, fields: &serde_json::Value) -> String {
+    let of = |field: &str| match &fields[field] {
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    match tool {
+        "runq_len" => format!("{tool} cpu{}", of("cpu")),
+        "bio_stat" => format!("{tool} {}", of("disk")),
+        "func_lat_hist" => format!("{tool} {}", of("func")),
+        _ => tool.to_string(),
+    }
+}
+
+// One or more lines (each ending with a comma), or None if there's nothing
+// to show of the event
+pub fn lines<Value>(tool: &str, event: &flaregun::Event<Value>) -> Option<String>
+where
+    Value: std::fmt::Display + crate::json::Fields,
+{
+    let task = crate::bytes_to_str(&event.task);
+    let pid = event.pid;
+    let ts = event.time.as_micros() as u64;
+    let value: f64 = event.value.to_string().parse().ok()?;
+    let mut args = serde_json::to_value(event.value.fields()).ok()?;
+    if let Some(stack) = &event.stack {
+        let frames = |frames: &[flaregun::Frame]| -> Vec<String> {
+            frames.iter().map(|f| f.to_string()).collect()
+        };
+        args["kernel_stack"] = frames(&stack.kernel).into();
+        args["user_stack"] = frames(&stack.user).into();
+    }
+    let lines = match tool {
+        tool if tool.ends_with("_lat") => {
+            let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let begin = serde_json::json!({
+                "ph": "b",
+                "name": tool,
+                "cat": tool,
+                "id": id,
+                "ts": ts.saturating_sub(value as u64),
+                "pid": pid,
+                "tid": pid,
+                "args": args,
+            });
+            let end = serde_json::json!({
+                "ph": "e",
+                "name": tool,
+                "cat": tool,
+                "id": id,
+                "ts": ts,
+                "pid": pid,
+                "tid": pid,
+            });
+            format!("{begin},\n{end}")
+        }
+        // Samples of stacks, as of when they're reported
+        "profile" => serde_json::json!({
+            "ph": "i",
+            "s": "t",
+            "name": tool,
+            "cat": tool,
+            "ts": ts,
+            "pid": pid,
+            "tid": pid,
+            "args": args,
+        })
+        .to_string(),
+        tool => serde_json::json!({
+            "ph": "C",
+            "name": counter_name(tool, &args),
+            "cat": tool,
+            "ts": ts,
+            "pid": pid,
+            "args": { "value": value },
+        })
+        .to_string(),
+    };
+    Some(match name(pid, task) {
+        Some(name) => format!("{name}\n{lines},"),
+        None => format!("{lines},"),
+    })
+}
@@ -1,7 +1,175 @@
 mod skel {
     include!(concat!(env!("OUT_DIR"), "/skel_bio_lat.rs"));
 }
-pub type Value = u64;
+
+/// The operation of a block i/o request, from the low bits of its `cmd_flags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BioOp {
+    Read,
+    Write,
+    Flush,
+    Discard,
+    SecureErase,
+    WriteZeroes,
+    Other(u8),
+}
+
+impl From<u32> for BioOp {
+    fn from(cmd_flags: u32) -> Self {
+        use BioOp::*;
+        match (cmd_flags & 0xff) as u8 {
+            0 => Read,
+            1 => Write,
+            2 => Flush,
+            3 => Discard,
+            5 => SecureErase,
+            9 => WriteZeroes,
+            op => Other(op),
+        }
+    }
+}
+
+impl From<BioOp> for u8 {
+    fn from(op: BioOp) -> Self {
+        use BioOp::*;
+        match op {
+            Read => 0,
+            Write => 1,
+            Flush => 2,
+            Discard => 3,
+            SecureErase => 5,
+            WriteZeroes => 9,
+            Other(op) => op,
+        }
+    }
+}
+
+impl std::fmt::Display for BioOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use BioOp::*;
+        match self {
+            Read => write!(f, "READ"),
+            Write => write!(f, "WRITE"),
+            Flush => write!(f, "FLUSH"),
+            Discard => write!(f, "DISCARD"),
+            SecureErase => write!(f, "SECURE_ERASE"),
+            WriteZeroes => write!(f, "WRITE_ZEROES"),
+            Other(op) => write!(f, "OP_{op}"),
+        }
+    }
+}
+
+/// Flags of a block i/o request. The BPF program normalizes these, because
+/// their bit positions in `cmd_flags` change between kernels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BioFlags(u32);
+
+impl BioFlags {
+    pub const SYNC: Self = Self(crate::bpf_constants::REQ_SYNC);
+    pub const META: Self = Self(crate::bpf_constants::REQ_META);
+    pub const FUA: Self = Self(crate::bpf_constants::REQ_FUA);
+    pub const PREFLUSH: Self = Self(crate::bpf_constants::REQ_PREFLUSH);
+    pub const RAHEAD: Self = Self(crate::bpf_constants::REQ_RAHEAD);
+
+    const NAMED: [(Self, &'static str); 5] = [
+        (Self::SYNC, "SYNC"),
+        (Self::META, "META"),
+        (Self::FUA, "FUA"),
+        (Self::PREFLUSH, "PREFLUSH"),
+        (Self::RAHEAD, "RAHEAD"),
+    ];
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// As normalized by the BPF program, e.g. for storing and loading.
+impl From<u32> for BioFlags {
+    fn from(flags: u32) -> Self {
+        Self(flags)
+    }
+}
+
+impl From<BioFlags> for u32 {
+    fn from(flags: BioFlags) -> Self {
+        flags.0
+    }
+}
+
+impl std::fmt::Display for BioFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut names = Self::NAMED
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| name);
+        if let Some(name) = names.next() {
+            write!(f, "{name}")?;
+        }
+        for name in names {
+            write!(f, "|{name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A completed block i/o request. The device number is encoded as in the
+/// kernel's `MKDEV`. The queue latency is only known when the request was
+/// traced from its insertion into the queue.
+#[derive(Debug, Clone, Copy)]
+pub struct Bio {
+    pub lat_us: u64,
+    pub q_lat_us: u64,
+    pub sector: u64,
+    pub len: u32,
+    pub dev: u32,
+    pub op: BioOp,
+    pub flags: BioFlags,
+}
+
+impl Bio {
+    /// The request's operation and flags, as abbreviated by blktrace.
+    /// E.g. "WS" for a synchronous write, or "FWFS" for a synchronous write
+    /// with a flush before and force-unit-access.
+    pub fn rwbs(&self) -> String {
+        let mut rwbs = String::new();
+        if self.flags.contains(BioFlags::PREFLUSH) {
+            rwbs.push('F');
+        }
+        rwbs.push_str(match self.op {
+            BioOp::Write => "W",
+            BioOp::Discard => "D",
+            BioOp::SecureErase => "DE",
+            BioOp::Flush => "F",
+            BioOp::Read => "R",
+            _ => "N",
+        });
+        for (flag, c) in [
+            (BioFlags::FUA, 'F'),
+            (BioFlags::RAHEAD, 'A'),
+            (BioFlags::SYNC, 'S'),
+            (BioFlags::META, 'M'),
+        ] {
+            if self.flags.contains(flag) {
+                rwbs.push(c);
+            }
+        }
+        rwbs
+    }
+
+    /// The disk's name, or its "major:minor" numbers if it has no name.
+    pub fn disk(&self) -> String {
+        crate::disk::name_or_numbers(self.dev)
+    }
+}
+
+impl std::fmt::Display for Bio {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.lat_us)
+    }
+}
+
+pub type Value = Bio;
 pub struct BioLat<'cls> {
     // Need to hold this to keep the attached probes alive
     _skel: skel::BioLatSkel<'cls>,
@@ -9,6 +177,50 @@ pub struct BioLat<'cls> {
     rx: std::sync::mpsc::Receiver<crate::event::Event<Value>>,
 }
 unsafe impl plain::Plain for skel::bio_lat_types::event {}
-crate::event::impl_from_bytes_for!(BioLat<'_>, Value, skel::bio_lat_types::event);
+
+impl crate::event::FromBytes<Value> for BioLat<'_> {
+    fn from_bytes(data: &[u8]) -> crate::event::Event<Value> {
+        let mut event = skel::bio_lat_types::event::default();
+        plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+        crate::event::Event {
+            time: crate::time::since_prog_start(event.ts),
+            task: event.task,
+            pid: event.pid,
+            value: Bio {
+                lat_us: event.lat_us,
+                q_lat_us: event.q_lat_us,
+                sector: event.sector,
+                len: event.len,
+                dev: event.dev,
+                op: BioOp::from(event.cmd_flags),
+                flags: BioFlags(event.flags),
+            },
+            stack: None,
+        }
+    }
+
+    fn stack_ids(data: &[u8]) -> Option<crate::stack::StackIds> {
+        let mut event = skel::bio_lat_types::event::default();
+        plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+        Some(crate::stack::StackIds {
+            pid: event.stack_pid,
+            kernel: event.kern_stack_id,
+            user: event.user_stack_id,
+        })
+    }
+}
+
 crate::stream::impl_stream_for!(BioLat<'_>, Value);
-crate::tool::impl_tool_for_perf_event_bpf_prog!(BioLat, skel::BioLatSkelBuilder);
+crate::tool::impl_tool_for_perf_event_bpf_prog!(
+    BioLat,
+    skel::BioLatSkelBuilder,
+    |open_skel, cfg| {
+        open_skel.rodata_mut().targ_dev = cfg.targ_dev as u32;
+        open_skel.rodata_mut().targ_filter_dev = cfg.targ_filter_dev;
+        open_skel.rodata_mut().targ_stacks = cfg.targ_stacks;
+        if let Some(op) = cfg.targ_bio_op {
+            open_skel.rodata_mut().targ_filter_op = true;
+            open_skel.rodata_mut().targ_op = op.into();
+        }
+    }
+);
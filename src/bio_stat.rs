@@ -0,0 +1,136 @@
+mod skel {
+    include!(concat!(env!("OUT_DIR"), "/skel_bio_stat.rs"));
+}
+
+/// Block i/o completed on a disk, on behalf of a process, in one reporting
+/// interval. The device number is encoded as in the kernel's `MKDEV`.
+/// Discards include secure erases and zeroed writes.
+/// Flushes include writes which requested a flush beforehand.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskStat {
+    pub dev: u32,
+    pub iops: f32,
+    pub bytes: u64,
+    pub in_flight: i64,
+    pub reads: u64,
+    pub writes: u64,
+    pub discards: u64,
+    pub flushes: u64,
+}
+
+impl DiskStat {
+    /// The disk's name, or its "major:minor" numbers if it has no name.
+    pub fn disk(&self) -> String {
+        crate::disk::name_or_numbers(self.dev)
+    }
+}
+
+impl std::fmt::Display for DiskStat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:00.02}", self.iops)
+    }
+}
+
+pub type Value = DiskStat;
+pub struct BioStat<'cls> {
+    // Need to hold this to keep the attached probes alive
+    skel: skel::BioStatSkel<'cls>,
+    interval: std::time::Duration,
+    next_report: std::time::Instant,
+    pending: std::collections::VecDeque<crate::event::Event<Value>>,
+}
+unsafe impl plain::Plain for skel::bio_stat_types::stat_key {}
+unsafe impl plain::Plain for skel::bio_stat_types::disk_stat {}
+unsafe impl plain::Plain for skel::bio_stat_types::disk_in_flight {}
+
+impl BioStat<'_> {
+    fn collect(&mut self) -> Vec<crate::event::Event<Value>> {
+        use libbpf_rs::MapFlags;
+        let time = crate::time::now();
+        let secs = self.interval.as_secs_f32();
+        let maps = self.skel.maps();
+        // Collected up front, as in `crate::stream::drain`. Disks with requests in
+        // flight are reported, whether or not any completed.
+        let keys: std::collections::BTreeSet<Vec<u8>> =
+            maps.stats().keys().chain(maps.in_flight().keys()).collect();
+        let mut evs = Vec::with_capacity(keys.len());
+        for key in keys {
+            let mut k = skel::bio_stat_types::stat_key::default();
+            let mut s = skel::bio_stat_types::disk_stat::default();
+            let mut f = skel::bio_stat_types::disk_in_flight::default();
+            plain::copy_from_bytes(&mut k, &key).expect("Data buffer was too short");
+            // In one step, so that none are counted between reading and clearing
+            match maps.stats().lookup_and_delete(&key) {
+                Ok(Some(data)) => {
+                    plain::copy_from_bytes(&mut s, &data).expect("Data buffer was too short")
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    log::error!("Error reading disk stats: {:?}", e);
+                    break;
+                }
+            }
+            // Only read, as requests are counted (and decremented) as they're issued
+            // (and completed)
+            match maps.in_flight().lookup(&key, MapFlags::ANY) {
+                Ok(Some(data)) => {
+                    plain::copy_from_bytes(&mut f, &data).expect("Data buffer was too short")
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    log::error!("Error reading requests in flight: {:?}", e);
+                    break;
+                }
+            }
+            if s.ios == 0 && f.count == 0 {
+                continue;
+            }
+            evs.push(crate::event::Event {
+                time,
+                task: match s.ios {
+                    0 => f.task,
+                    _ => s.task,
+                },
+                pid: k.pid,
+                value: DiskStat {
+                    dev: k.dev,
+                    iops: s.ios as f32 / secs,
+                    bytes: s.bytes,
+                    in_flight: f.count,
+                    reads: s.reads,
+                    writes: s.writes,
+                    discards: s.discards,
+                    flushes: s.flushes,
+                },
+                stack: None,
+            });
+        }
+        evs
+    }
+}
+
+impl crate::tool::Tool for BioStat<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        use crate::tool::Error;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::Skel;
+        use libbpf_rs::skel::SkelBuilder;
+        let skel_builder = skel::BioStatSkelBuilder::default();
+        let mut open_skel = skel_builder.open().map_err(|_| Error::Libbpf)?;
+        open_skel.rodata_mut().targ_pid = cfg.targ_pid;
+        open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+        open_skel.rodata_mut().targ_dev = cfg.targ_dev as u32;
+        open_skel.rodata_mut().targ_filter_dev = cfg.targ_filter_dev;
+        let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+        skel.attach().map_err(|_| Error::Libbpf)?;
+        let interval = std::time::Duration::from_millis(cfg.targ_reporting_interval_ms);
+        Ok(Self {
+            skel,
+            interval,
+            next_report: std::time::Instant::now() + interval,
+            pending: std::collections::VecDeque::new(),
+        })
+    }
+}
+
+crate::stream::impl_interval_stream_for!(BioStat<'_>, Value);
@@ -2,6 +2,7 @@
 // Copyright (c) 2020 Wenbo Zhang
 #include "constants.h"
 #include "core_fixes.bpf.h"
+#include "stack_traces.bpf.h"
 #include "vmlinux.h"
 #include <bpf/bpf_core_read.h>
 #include <bpf/bpf_helpers.h>
@@ -16,13 +17,19 @@
 
 #define MKDEV(ma, mi) (((ma) << MINORBITS) | (mi))
 
+#define REQ_OP_BITS 8
+#define REQ_OP_MASK ((1 << REQ_OP_BITS) - 1)
+
 volatile bool const targ_filter_cgroup = false;
 volatile bool const targ_filter_queued = false;
 volatile bool const targ_filter_dev = false;
+volatile bool const targ_filter_op = false;
 volatile const __u32 targ_dev = 0;
+volatile const __u8 targ_op = 0;
 volatile const pid_t targ_pid = 0;
 volatile const pid_t targ_tgid = 0;
 volatile const __u64 min_lat_us = 0;
+volatile const bool targ_stacks = false;
 
 struct event {
   u8 task[FL_TASK_COMM_LEN];
@@ -34,6 +41,10 @@ struct event {
   __u32 pid;
   __u32 cmd_flags;
   __u32 dev;
+  __u32 flags;
+  __s32 kern_stack_id;
+  __s32 user_stack_id;
+  __u32 stack_pid;
 } _event = {};
 
 extern __u32 LINUX_KERNEL_VERSION __kconfig;
@@ -54,6 +65,9 @@ struct stage {
   u64 insert;
   u64 issue;
   __u32 dev;
+  __s32 kern_stack_id;
+  __s32 user_stack_id;
+  __u32 stack_pid;
 };
 
 struct {
@@ -69,6 +83,27 @@ struct {
   __uint(value_size, sizeof(u32));
 } events SEC(".maps");
 
+/**
+ * The bit positions of request flags in cmd_flags change between kernels,
+ * so they're normalized to the (stable) FL_REQ_* flags for userspace.
+ */
+static __always_inline __u32 get_req_flags(__u32 cmd_flags)
+{
+  __u32 flags = 0;
+
+  if (cmd_flags & (1U << bpf_core_enum_value(enum req_flag_bits, __REQ_SYNC)))
+    flags |= FL_REQ_SYNC;
+  if (cmd_flags & (1U << bpf_core_enum_value(enum req_flag_bits, __REQ_META)))
+    flags |= FL_REQ_META;
+  if (cmd_flags & (1U << bpf_core_enum_value(enum req_flag_bits, __REQ_FUA)))
+    flags |= FL_REQ_FUA;
+  if (cmd_flags & (1U << bpf_core_enum_value(enum req_flag_bits, __REQ_PREFLUSH)))
+    flags |= FL_REQ_PREFLUSH;
+  if (cmd_flags & (1U << bpf_core_enum_value(enum req_flag_bits, __REQ_RAHEAD)))
+    flags |= FL_REQ_RAHEAD;
+  return flags;
+}
+
 static __always_inline int current_piddata(struct request* rq, struct piddata* piddata)
 {
   u64 pid_tgid = bpf_get_current_pid_tgid();
@@ -82,7 +117,7 @@ static __always_inline int current_piddata(struct request* rq, struct piddata* p
   return 0;
 }
 
-static __always_inline int trace_rq_start(struct request* rq, bool insert)
+static __always_inline int trace_rq_start(void* ctx, struct request* rq, bool insert)
 {
   struct stage *stagep, stage = {};
   u64 ts = bpf_ktime_get_ns();
@@ -94,6 +129,11 @@ static __always_inline int trace_rq_start(struct request* rq, bool insert)
     stage.dev = disk ? MKDEV(BPF_CORE_READ(disk, major), BPF_CORE_READ(disk, first_minor)) : 0;
     if (targ_filter_dev && targ_dev != stage.dev)
       return 0;
+    /* the stack which submitted the request, completion happens elsewhere */
+    stage.kern_stack_id = -1;
+    stage.user_stack_id = -1;
+    if (targ_stacks)
+      get_stack_ids(ctx, &stage.kern_stack_id, &stage.user_stack_id, &stage.stack_pid);
     stagep = &stage;
   }
   if (insert)
@@ -119,9 +159,9 @@ int BPF_PROG(block_rq_insert)
    * to TP_PROTO(struct request *rq)
    */
   if (LINUX_KERNEL_VERSION >= KERNEL_VERSION(5, 11, 0))
-    return trace_rq_start((void*)ctx[0], true);
+    return trace_rq_start(ctx, (void*)ctx[0], true);
   else
-    return trace_rq_start((void*)ctx[1], true);
+    return trace_rq_start(ctx, (void*)ctx[1], true);
 }
 
 SEC("tp_btf/block_rq_issue")
@@ -137,9 +177,9 @@ int BPF_PROG(block_rq_issue)
    * to TP_PROTO(struct request *rq)
    */
   if (LINUX_KERNEL_VERSION >= KERNEL_VERSION(5, 11, 0))
-    return trace_rq_start((void*)ctx[0], false);
+    return trace_rq_start(ctx, (void*)ctx[0], false);
   else
-    return trace_rq_start((void*)ctx[1], false);
+    return trace_rq_start(ctx, (void*)ctx[1], false);
 }
 
 SEC("tp_btf/block_rq_complete")
@@ -152,11 +192,15 @@ int BPF_PROG(block_rq_complete, struct request* rq, int error, unsigned int nr_b
   u64 ts = bpf_ktime_get_ns();
   struct event event = {};
   struct stage* stagep;
+  u32 cmd_flags;
   s64 delta;
 
   stagep = bpf_map_lookup_elem(&start, &rq);
   if (! stagep)
     return 0;
+  cmd_flags = BPF_CORE_READ(rq, cmd_flags);
+  if (targ_filter_op && (cmd_flags & REQ_OP_MASK) != targ_op)
+    goto cleanup;
   delta = (s64)(ts - stagep->issue);
   u64 delta_us = delta / 1000ul;
   if (delta < 0 || delta_us < min_lat_us)
@@ -176,8 +220,12 @@ int BPF_PROG(block_rq_complete, struct request* rq, int error, unsigned int nr_b
   event.ts = ts;
   event.sector = BPF_CORE_READ(rq, __sector);
   event.len = BPF_CORE_READ(rq, __data_len);
-  event.cmd_flags = BPF_CORE_READ(rq, cmd_flags);
+  event.cmd_flags = cmd_flags;
   event.dev = stagep->dev;
+  event.flags = get_req_flags(cmd_flags);
+  event.kern_stack_id = stagep->kern_stack_id;
+  event.user_stack_id = stagep->user_stack_id;
+  event.stack_pid = stagep->stack_pid;
   bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
 
 cleanup:
@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "constants.h"
+#include "core_fixes.bpf.h"
+#include "vmlinux.h"
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define MAX_ENTRIES 10240
+
+#define MINORBITS 20
+#define MINORMASK ((1U << MINORBITS) - 1)
+
+#define MKDEV(ma, mi) (((ma) << MINORBITS) | (mi))
+
+#define REQ_OP_BITS 8
+#define REQ_OP_MASK ((1 << REQ_OP_BITS) - 1)
+
+volatile bool const targ_filter_dev = false;
+volatile const __u32 targ_dev = 0;
+volatile const pid_t targ_pid = 0;
+volatile const pid_t targ_tgid = 0;
+
+struct stat_key {
+  __u32 dev;
+  __u32 pid;
+} _stat_key = {};
+
+/* counted over a reporting interval, and drained by userspace */
+struct disk_stat {
+  u8 task[FL_TASK_COMM_LEN];
+  __u64 ios;
+  __u64 bytes;
+  __u64 reads;
+  __u64 writes;
+  __u64 discards;
+  __u64 flushes;
+} _disk_stat = {};
+
+/* only ever changed here, atomically, and never by userspace */
+struct disk_in_flight {
+  u8 task[FL_TASK_COMM_LEN];
+  __s64 count;
+} _disk_in_flight = {};
+
+extern __u32 LINUX_KERNEL_VERSION __kconfig;
+
+struct stage {
+  struct stat_key key;
+  bool issued;
+  u8 task[FL_TASK_COMM_LEN];
+};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, struct request*);
+  __type(value, struct stage);
+} start SEC(".maps");
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, struct stat_key);
+  __type(value, struct disk_stat);
+} stats SEC(".maps");
+
+/**
+ * entries are never deleted, as one could be while a request is issued, so the
+ * least recently used are evicted instead, once it's full
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_LRU_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, struct stat_key);
+  __type(value, struct disk_in_flight);
+} in_flight SEC(".maps");
+
+static __always_inline struct disk_stat* get_or_init_stat(struct stage* stagep)
+{
+  struct disk_stat* statp = bpf_map_lookup_elem(&stats, &stagep->key);
+
+  if (! statp) {
+    struct disk_stat stat = {};
+
+    __builtin_memcpy(&stat.task, &stagep->task, sizeof(stat.task));
+    bpf_map_update_elem(&stats, &stagep->key, &stat, BPF_NOEXIST);
+    statp = bpf_map_lookup_elem(&stats, &stagep->key);
+  }
+  return statp;
+}
+
+static __always_inline struct disk_in_flight* get_or_init_in_flight(struct stage* stagep)
+{
+  struct disk_in_flight* in_flightp = bpf_map_lookup_elem(&in_flight, &stagep->key);
+
+  if (! in_flightp) {
+    struct disk_in_flight f = {};
+
+    __builtin_memcpy(&f.task, &stagep->task, sizeof(f.task));
+    bpf_map_update_elem(&in_flight, &stagep->key, &f, BPF_NOEXIST);
+    in_flightp = bpf_map_lookup_elem(&in_flight, &stagep->key);
+  }
+  return in_flightp;
+}
+
+static __always_inline int trace_rq_start(struct request* rq, bool issue)
+{
+  struct stage *stagep, stage = {};
+  struct disk_in_flight* in_flightp;
+
+  stagep = bpf_map_lookup_elem(&start, &rq);
+  if (! stagep) {
+    struct gendisk* disk = get_disk(rq);
+    u64 pid_tgid = bpf_get_current_pid_tgid();
+    u32 pid = pid_tgid >> 32;
+    u32 tgid = (u32)pid_tgid;
+
+    if (targ_pid && pid != targ_pid)
+      return 0;
+    if (targ_tgid && tgid != targ_tgid)
+      return 0;
+    stage.key.dev = disk ? MKDEV(BPF_CORE_READ(disk, major), BPF_CORE_READ(disk, first_minor)) : 0;
+    if (targ_filter_dev && targ_dev != stage.key.dev)
+      return 0;
+    stage.key.pid = pid;
+    bpf_get_current_comm(&stage.task, sizeof(stage.task));
+    stagep = &stage;
+  }
+  if (issue && ! stagep->issued) {
+    in_flightp = get_or_init_in_flight(stagep);
+    if (in_flightp) {
+      stagep->issued = true;
+      __sync_fetch_and_add(&in_flightp->count, 1);
+    }
+  }
+  if (stagep == &stage) {
+    bpf_map_update_elem(&start, &rq, stagep, 0);
+  }
+  return 0;
+}
+
+SEC("tp_btf/block_rq_insert")
+
+int BPF_PROG(block_rq_insert)
+{
+  /**
+   * commit a54895fa (v5.11-rc1) changed tracepoint argument list
+   * from TP_PROTO(struct request_queue *q, struct request *rq)
+   * to TP_PROTO(struct request *rq)
+   */
+  if (LINUX_KERNEL_VERSION >= KERNEL_VERSION(5, 11, 0))
+    return trace_rq_start((void*)ctx[0], false);
+  else
+    return trace_rq_start((void*)ctx[1], false);
+}
+
+SEC("tp_btf/block_rq_issue")
+
+int BPF_PROG(block_rq_issue)
+{
+  /**
+   * commit a54895fa (v5.11-rc1) changed tracepoint argument list
+   * from TP_PROTO(struct request_queue *q, struct request *rq)
+   * to TP_PROTO(struct request *rq)
+   */
+  if (LINUX_KERNEL_VERSION >= KERNEL_VERSION(5, 11, 0))
+    return trace_rq_start((void*)ctx[0], true);
+  else
+    return trace_rq_start((void*)ctx[1], true);
+}
+
+SEC("tp_btf/block_rq_complete")
+
+int BPF_PROG(block_rq_complete, struct request* rq, int error, unsigned int nr_bytes)
+{
+  struct disk_in_flight* in_flightp;
+  struct disk_stat* statp;
+  struct stage* stagep;
+  u32 cmd_flags, op;
+
+  stagep = bpf_map_lookup_elem(&start, &rq);
+  if (! stagep)
+    return 0;
+  if (stagep->issued) {
+    in_flightp = bpf_map_lookup_elem(&in_flight, &stagep->key);
+    if (in_flightp)
+      __sync_fetch_and_add(&in_flightp->count, -1);
+  }
+  statp = get_or_init_stat(stagep);
+  if (! statp)
+    goto cleanup;
+
+  cmd_flags = BPF_CORE_READ(rq, cmd_flags);
+  op = cmd_flags & REQ_OP_MASK;
+  __sync_fetch_and_add(&statp->ios, 1);
+  __sync_fetch_and_add(&statp->bytes, nr_bytes);
+  if (op == REQ_OP_READ)
+    __sync_fetch_and_add(&statp->reads, 1);
+  else if (op == REQ_OP_WRITE)
+    __sync_fetch_and_add(&statp->writes, 1);
+  else if (op == REQ_OP_DISCARD || op == REQ_OP_SECURE_ERASE || op == REQ_OP_WRITE_ZEROES)
+    __sync_fetch_and_add(&statp->discards, 1);
+  if (op == REQ_OP_FLUSH || cmd_flags & (1U << bpf_core_enum_value(enum req_flag_bits, __REQ_PREFLUSH)))
+    __sync_fetch_and_add(&statp->flushes, 1);
+
+cleanup:
+  bpf_map_delete_elem(&start, &rq);
+  return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -1,2 +1,9 @@
 #pragma once
 #define FL_TASK_COMM_LEN 20
+#define FL_REQ_SYNC (1 << 0)
+#define FL_REQ_META (1 << 1)
+#define FL_REQ_FUA (1 << 2)
+#define FL_REQ_PREFLUSH (1 << 3)
+#define FL_REQ_RAHEAD (1 << 4)
+#define FL_MAX_SLOTS 27
+#define FL_MAX_STACK_DEPTH 127
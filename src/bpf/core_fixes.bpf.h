@@ -303,4 +303,53 @@ static __always_inline __u8 get_inet_sock_bind_address_no_port(void* inet_sock)
   return (1 << INET_FLAGS_BIND_ADDRESS_NO_PORT___x) & inet_flags ? 1 : 0;
 }
 
+/**
+ * commit bcf9033e5449 ("sched: move CPU field back into thread_info if
+ * THREAD_INFO_IN_TASK=y") moves task_struct::cpu to
+ * task_struct::thread_info::cpu
+ * see:
+ *     https://github.com/torvalds/linux/commit/bcf9033e5449
+ */
+struct thread_info___x {
+  unsigned int cpu;
+} __attribute__((preserve_access_index));
+
+struct task_struct___cpu_o {
+  unsigned int cpu;
+} __attribute__((preserve_access_index));
+
+struct task_struct___cpu_x {
+  struct thread_info___x thread_info;
+} __attribute__((preserve_access_index));
+
+static __always_inline __u32 get_task_cpu(void* task)
+{
+  struct task_struct___cpu_x* t = task;
+
+  if (bpf_core_field_exists(t->thread_info.cpu))
+    return BPF_CORE_READ(t, thread_info.cpu);
+  return BPF_CORE_READ((struct task_struct___cpu_o*)task, cpu);
+}
+
+/**
+ * Recent kernels rename cfs_rq::nr_running to cfs_rq::nr_queued, as it counts
+ * the entities queued on the run queue (including delayed ones).
+ */
+struct cfs_rq___o {
+  unsigned int nr_running;
+} __attribute__((preserve_access_index));
+
+struct cfs_rq___x {
+  unsigned int nr_queued;
+} __attribute__((preserve_access_index));
+
+static __always_inline __u64 get_cfs_rq_nr_queued(void* cfs_rq)
+{
+  struct cfs_rq___x* q = cfs_rq;
+
+  if (bpf_core_field_exists(q->nr_queued))
+    return BPF_CORE_READ(q, nr_queued);
+  return BPF_CORE_READ((struct cfs_rq___o*)cfs_rq, nr_running);
+}
+
 #endif /* __CORE_FIXES_BPF_H */
@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "constants.h"
+#include "core_fixes.bpf.h"
+#include "vmlinux.h"
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define MAX_ENTRIES 10240
+
+volatile const pid_t targ_pid = 0;
+volatile const pid_t targ_tgid = 0;
+
+struct migrations {
+  u8 task[FL_TASK_COMM_LEN];
+  __u64 count;
+  __u32 orig_cpu;
+  __u32 dest_cpu;
+} _migrations = {};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, u32);
+  __type(value, struct migrations);
+} migrations SEC(".maps");
+
+SEC("tp_btf/sched_migrate_task")
+
+int BPF_PROG(sched_migrate_task, struct task_struct* p, int dest_cpu)
+{
+  /* the user-facing pid is the kernel's tgid, and vice versa */
+  u32 pid = p->tgid;
+  u32 tgid = p->pid;
+  struct migrations* mp;
+
+  if (! pid)
+    return 0;
+  if (targ_pid && targ_pid != pid)
+    return 0;
+  if (targ_tgid && targ_tgid != tgid)
+    return 0;
+
+  mp = bpf_map_lookup_elem(&migrations, &pid);
+  if (! mp) {
+    struct migrations m = {};
+
+    bpf_probe_read_kernel_str(&m.task, sizeof(m.task), p->comm);
+    bpf_map_update_elem(&migrations, &pid, &m, BPF_NOEXIST);
+    mp = bpf_map_lookup_elem(&migrations, &pid);
+    if (! mp)
+      return 0;
+  }
+  __sync_fetch_and_add(&mp->count, 1);
+  mp->orig_cpu = get_task_cpu(p);
+  mp->dest_cpu = dest_cpu;
+  return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
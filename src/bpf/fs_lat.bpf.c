@@ -1,6 +1,7 @@
 /* SPDX-License-Identifier: GPL-2.0 */
 /* Copyright (c) 2020 Wenbo Zhang */
 #include "constants.h"
+#include "stack_traces.bpf.h"
 #include "vmlinux.h"
 #include <bpf/bpf_core_read.h>
 #include <bpf/bpf_helpers.h>
@@ -13,6 +14,7 @@ volatile const __u64 min_lat_us = 0;
 volatile const pid_t targ_tgid = 0;
 // todo ^
 volatile const pid_t targ_pid = 0;
+volatile const bool targ_stacks = false;
 
 #ifdef ALLOW_UNSAFE_ENUM
 enum fs_file_op {
@@ -31,13 +33,16 @@ static const u8 F_FSYNC = 3;
 
 struct event {
   __u64 lat_us;
-  __u64 end_ns;
+  __u64 ts;
   __s64 offset;
   ssize_t size;
   pid_t pid;
   u8 op;
   u8 file[FILE_NAME_LEN];
   u8 task[FL_TASK_COMM_LEN];
+  __s32 kern_stack_id;
+  __s32 user_stack_id;
+  __u32 stack_pid;
 } _event = {};
 
 struct data {
@@ -108,7 +113,7 @@ static int probe_exit(void* ctx, u8 op, ssize_t size)
     return 0;
 
   event.lat_us = delta_us;
-  event.end_ns = end_ns;
+  event.ts = end_ns;
   event.offset = datap->start;
   if (op != F_FSYNC)
     event.size = size;
@@ -121,6 +126,10 @@ static int probe_exit(void* ctx, u8 op, ssize_t size)
   file_name = BPF_CORE_READ(dentry, d_name.name);
   bpf_probe_read_kernel_str(&event.file, sizeof(event.file), file_name);
   bpf_get_current_comm(&event.task, sizeof(event.task));
+  event.kern_stack_id = -1;
+  event.user_stack_id = -1;
+  if (targ_stacks)
+    get_stack_ids(ctx, &event.kern_stack_id, &event.user_stack_id, &event.stack_pid);
   bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
   return 0;
 }
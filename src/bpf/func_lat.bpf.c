@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "constants.h"
+#include "vmlinux.h"
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define MAX_ENTRIES 10240
+#define MAX_DEPTH 8
+
+volatile const __u64 min_lat_us = 0;
+volatile const pid_t targ_pid = 0;
+volatile const pid_t targ_tgid = 0;
+volatile const bool targ_hist = false;
+
+struct event {
+  u8 task[FL_TASK_COMM_LEN];
+  __u64 lat_us;
+  __u64 ts;
+  __u64 ip;
+  pid_t pid;
+} _event = {};
+
+struct hist {
+  __u64 slots[FL_MAX_SLOTS];
+} _hist = {};
+
+struct start {
+  __u64 ts;
+  __u64 ip;
+};
+
+/**
+ * with a glob, a matching function can call another (or itself), so each
+ * thread has a stack of the calls it's in, and the calls deeper than it are
+ * skipped (but still counted, to match their exits)
+ */
+struct starts {
+  __u32 depth;
+  struct start calls[MAX_DEPTH];
+};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, u32);
+  __type(value, struct starts);
+} starts SEC(".maps");
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, u64);
+  __type(value, struct hist);
+} hists SEC(".maps");
+
+struct {
+  __uint(type, BPF_MAP_TYPE_PERF_EVENT_ARRAY);
+  __uint(key_size, sizeof(u32));
+  __uint(value_size, sizeof(u32));
+} events SEC(".maps");
+
+static __always_inline u64 log2l(u64 v)
+{
+  u64 r = 0;
+
+  for (int i = 0; i < 64 && v > 1; i++) {
+    v >>= 1;
+    r++;
+  }
+  return r;
+}
+
+static int probe_entry(u64 ip)
+{
+  u64 pid_tgid = bpf_get_current_pid_tgid();
+  u32 pid = pid_tgid >> 32;
+  u32 tid = (u32)pid_tgid;
+  static const struct starts zero;
+  struct starts* startsp;
+  u32 depth;
+
+  if (targ_pid && targ_pid != pid)
+    return 0;
+  if (targ_tgid && targ_tgid != tid)
+    return 0;
+
+  startsp = bpf_map_lookup_elem(&starts, &tid);
+  if (! startsp) {
+    bpf_map_update_elem(&starts, &tid, &zero, BPF_NOEXIST);
+    startsp = bpf_map_lookup_elem(&starts, &tid);
+    if (! startsp)
+      return 0;
+  }
+  depth = startsp->depth;
+  if (depth < MAX_DEPTH) {
+    startsp->calls[depth].ts = bpf_ktime_get_ns();
+    startsp->calls[depth].ip = ip;
+  }
+  startsp->depth = depth + 1;
+  return 0;
+}
+
+static int probe_exit(void* ctx)
+{
+  u64 pid_tgid = bpf_get_current_pid_tgid();
+  u32 pid = pid_tgid >> 32;
+  u32 tid = (u32)pid_tgid;
+  static const struct hist zero;
+  struct event event = {};
+  struct starts* startsp;
+  struct hist* histp;
+  u64 ts, lat_us, ip, slot;
+  u32 depth;
+
+  startsp = bpf_map_lookup_elem(&starts, &tid);
+  if (! startsp || ! startsp->depth)
+    return 0; /* missed entry */
+
+  ts = bpf_ktime_get_ns();
+  depth = startsp->depth - 1;
+  startsp->depth = depth;
+  if (depth >= MAX_DEPTH)
+    return 0; /* skipped, too deep */
+  lat_us = (ts - startsp->calls[depth].ts) / 1000;
+  ip = startsp->calls[depth].ip;
+  if (! depth)
+    bpf_map_delete_elem(&starts, &tid);
+  if (min_lat_us && lat_us <= min_lat_us)
+    return 0;
+
+  if (targ_hist) {
+    histp = bpf_map_lookup_elem(&hists, &ip);
+    if (! histp) {
+      bpf_map_update_elem(&hists, &ip, &zero, BPF_NOEXIST);
+      histp = bpf_map_lookup_elem(&hists, &ip);
+      if (! histp)
+        return 0;
+    }
+    slot = log2l(lat_us);
+    if (slot >= FL_MAX_SLOTS)
+      slot = FL_MAX_SLOTS - 1;
+    __sync_fetch_and_add(&histp->slots[slot], 1);
+    return 0;
+  }
+
+  event.lat_us = lat_us;
+  event.ts = ts;
+  event.ip = ip;
+  event.pid = pid;
+  bpf_get_current_comm(&event.task, sizeof(event.task));
+  bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
+  return 0;
+}
+
+SEC("fentry")
+
+int BPF_PROG(fentry_entry)
+{
+  return probe_entry(bpf_get_func_ip(ctx));
+}
+
+SEC("fexit")
+
+int BPF_PROG(fexit_exit)
+{
+  return probe_exit(ctx);
+}
+
+SEC("kprobe")
+
+int BPF_KPROBE(kprobe_entry)
+{
+  return probe_entry(PT_REGS_IP(ctx));
+}
+
+SEC("kretprobe")
+
+int BPF_KRETPROBE(kprobe_exit)
+{
+  return probe_exit(ctx);
+}
+
+char LICENSE[] SEC("license") = "GPL";
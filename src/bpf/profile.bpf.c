@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "constants.h"
+#include "stack_traces.bpf.h"
+#include "vmlinux.h"
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define MAX_ENTRIES 10240
+
+volatile const pid_t targ_pid = 0;
+volatile const pid_t targ_tgid = 0;
+
+struct profile_key {
+  u8 task[FL_TASK_COMM_LEN];
+  __s32 kern_stack_id;
+  __s32 user_stack_id;
+  __u32 pid;
+} _profile_key = {};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, struct profile_key);
+  __type(value, u64);
+} counts SEC(".maps");
+
+SEC("perf_event")
+
+int do_sample(struct bpf_perf_event_data* ctx)
+{
+  u64 pid_tgid = bpf_get_current_pid_tgid();
+  u32 pid = pid_tgid >> 32;
+  u32 tid = (u32)pid_tgid;
+  struct profile_key key = {};
+  static const u64 zero;
+  u64* countp;
+
+  /* idle */
+  if (! tid)
+    return 0;
+  if (targ_pid && targ_pid != pid)
+    return 0;
+  if (targ_tgid && targ_tgid != tid)
+    return 0;
+
+  get_stack_ids(ctx, &key.kern_stack_id, &key.user_stack_id, &key.pid);
+  bpf_get_current_comm(&key.task, sizeof(key.task));
+
+  countp = bpf_map_lookup_elem(&counts, &key);
+  if (! countp) {
+    bpf_map_update_elem(&counts, &key, &zero, BPF_NOEXIST);
+    countp = bpf_map_lookup_elem(&counts, &key);
+    if (! countp)
+      return 0;
+  }
+  __sync_fetch_and_add(countp, 1);
+  return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
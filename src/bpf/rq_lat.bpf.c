@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-2.0
 // Copyright (c) 2019 Facebook
 #include "constants.h"
+#include "stack_traces.bpf.h"
 #include "vmlinux.h"
 #include <bpf/bpf_core_read.h>
 #include <bpf/bpf_helpers.h>
@@ -10,11 +11,16 @@
 volatile const __u64 min_lat_us = 0;
 volatile const pid_t targ_pid = 0;
 volatile const pid_t targ_tgid = 0;
+volatile const bool targ_stacks = false;
 
 struct event {
   u8 task[FL_TASK_COMM_LEN];
   __u64 lat_us;
+  __u64 ts;
   pid_t pid;
+  __s32 kern_stack_id;
+  __s32 user_stack_id;
+  __u32 stack_pid;
 } _event = {0};
 
 // Dummy instance to get skeleton to generate definition for `struct event`
@@ -93,7 +99,7 @@ int handle__sched_switch(u64* ctx)
   struct task_struct* prev = (struct task_struct*)ctx[1];
   struct task_struct* next = (struct task_struct*)ctx[2];
   struct event event = {};
-  u64 *tsp, lat_us;
+  u64 *tsp, ts, lat_us;
   long state = get_task_state(prev);
   u32 pid;
 
@@ -108,14 +114,22 @@ int handle__sched_switch(u64* ctx)
   if (! tsp)
     return 0; /* missed enqueue */
 
-  lat_us = (bpf_ktime_get_ns() - *tsp) / 1000;
+  ts = bpf_ktime_get_ns();
+  lat_us = (ts - *tsp) / 1000;
   if (min_lat_us && lat_us <= min_lat_us)
     return 0;
 
   event.pid = pid;
   event.lat_us = lat_us;
+  event.ts = ts;
   bpf_probe_read_kernel_str(&event.task, sizeof(event.task), next->comm);
 
+  /* the stack of the task giving up the cpu, which (last) kept the waiting task off of it */
+  event.kern_stack_id = -1;
+  event.user_stack_id = -1;
+  if (targ_stacks)
+    get_stack_ids(ctx, &event.kern_stack_id, &event.user_stack_id, &event.stack_pid);
+
   /* output */
   bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
 
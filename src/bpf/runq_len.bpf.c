@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: GPL-2.0
+// Copyright (c) 2020 Wenbo Zhang
+#include "constants.h"
+#include "core_fixes.bpf.h"
+#include "vmlinux.h"
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define MAX_CPU_NR 1024
+
+struct runq_len {
+  __u64 sum;
+  __u64 samples;
+  __u64 max;
+} _runq_len = {};
+
+/* a hash, rather than an array, for userspace to read and delete in one step */
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_CPU_NR);
+  __type(key, u32);
+  __type(value, struct runq_len);
+} runq_lens SEC(".maps");
+
+SEC("perf_event")
+
+int do_sample(struct bpf_perf_event_data* ctx)
+{
+  struct task_struct* task = (void*)bpf_get_current_task();
+  u32 cpu = bpf_get_smp_processor_id();
+  struct runq_len* lenp;
+  u64 len;
+
+  lenp = bpf_map_lookup_elem(&runq_lens, &cpu);
+  if (! lenp) {
+    struct runq_len zero = {};
+
+    bpf_map_update_elem(&runq_lens, &cpu, &zero, BPF_NOEXIST);
+    lenp = bpf_map_lookup_elem(&runq_lens, &cpu);
+    if (! lenp)
+      return 0;
+  }
+
+  /* the currently running task is counted, but it isn't waiting */
+  len = get_cfs_rq_nr_queued(BPF_CORE_READ(task, se.cfs_rq));
+  if (len > 0)
+    len--;
+
+  lenp->sum += len;
+  lenp->samples++;
+  if (len > lenp->max)
+    lenp->max = len;
+  return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -0,0 +1,55 @@
+/* SPDX-License-Identifier: GPL-2.0 */
+#ifndef __STACK_TRACES_BPF_H
+#define __STACK_TRACES_BPF_H
+
+#include "constants.h"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+#define MAX_STACKS 16384
+/* Not in vmlinux.h, which has no macros */
+#define FL_EEXIST 17
+
+/**
+ * Stacks are looked up (and symbolized) in userspace by their ids. Identical
+ * stacks share an id. The profiler deletes the stacks it has read every
+ * interval, so that the map doesn't fill. A stack whose id is already taken
+ * by a different stack isn't stored, and is counted in stack_id_collisions.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_STACK_TRACE);
+  __uint(max_entries, MAX_STACKS);
+  __uint(key_size, sizeof(u32));
+  __uint(value_size, FL_MAX_STACK_DEPTH * sizeof(u64));
+} stack_traces SEC(".maps");
+
+struct {
+  __uint(type, BPF_MAP_TYPE_ARRAY);
+  __uint(max_entries, 1);
+  __type(key, u32);
+  __type(value, u64);
+} stack_id_collisions SEC(".maps");
+
+static __always_inline __s32 get_stack_id(void* ctx, u64 flags)
+{
+  static const u32 zero;
+  __s32 id = bpf_get_stackid(ctx, &stack_traces, flags);
+  u64* collisionsp;
+
+  if (id == -FL_EEXIST) {
+    collisionsp = bpf_map_lookup_elem(&stack_id_collisions, &zero);
+    if (collisionsp)
+      __sync_fetch_and_add(collisionsp, 1);
+  }
+  return id;
+}
+
+/* The kernel and user stacks of the current task, or negative if unavailable */
+static __always_inline void get_stack_ids(void* ctx, __s32* kern_stack_id, __s32* user_stack_id, __u32* stack_pid)
+{
+  *kern_stack_id = get_stack_id(ctx, 0);
+  *user_stack_id = get_stack_id(ctx, BPF_F_USER_STACK);
+  *stack_pid = bpf_get_current_pid_tgid() >> 32;
+}
+
+#endif /* __STACK_TRACES_BPF_H */
@@ -22,6 +22,7 @@ struct event {
   __u32 saddr[4];
   __u32 daddr[4];
   __u64 lat_us;
+  __u64 ts;
   pid_t pid;
   pid_t tid;
   __u16 dport;
@@ -86,7 +87,7 @@ static int handle_tcp_rcv_space_adjust(void* ctx, struct sock* sk)
 {
   const struct inet_sock* inet = (struct inet_sock*)(sk);
   u64 sock_ident = get_sock_ident(sk);
-  u64 id = bpf_get_current_pid_tgid(), *tsp;
+  u64 id = bpf_get_current_pid_tgid(), *tsp, ts;
   u32 pid = id >> 32, tid = id;
   struct event* eventp;
   s64 lat_us;
@@ -101,7 +102,8 @@ static int handle_tcp_rcv_space_adjust(void* ctx, struct sock* sk)
   if (targ_tgid && targ_tgid != tid)
     goto cleanup;
 
-  lat_us = (bpf_ktime_get_ns() - *tsp) / 1000;
+  ts = bpf_ktime_get_ns();
+  lat_us = (ts - *tsp) / 1000;
   if (lat_us < 0 || lat_us <= min_lat_us)
     goto cleanup;
 
@@ -112,6 +114,7 @@ static int handle_tcp_rcv_space_adjust(void* ctx, struct sock* sk)
   eventp->pid = pid;
   eventp->tid = tid;
   eventp->lat_us = lat_us;
+  eventp->ts = ts;
   eventp->sport = BPF_CORE_READ(inet, inet_sport);
   eventp->dport = BPF_CORE_READ(sk, __sk_common.skc_dport);
   __builtin_memset(eventp->task, 0, sizeof(eventp->task));
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "constants.h"
+#include "vmlinux.h"
+#include <bpf/bpf_core_read.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+
+#define MAX_ENTRIES 10240
+
+volatile const __u64 min_lat_us = 0;
+volatile const pid_t targ_pid = 0;
+volatile const pid_t targ_tgid = 0;
+
+struct event {
+  u8 task[FL_TASK_COMM_LEN];
+  __u64 lat_us;
+  __u64 ts;
+  pid_t pid;
+  pid_t tid;
+} _event = {};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_ENTRIES);
+  __type(key, u32);
+  __type(value, u64);
+} starts SEC(".maps");
+
+struct {
+  __uint(type, BPF_MAP_TYPE_PERF_EVENT_ARRAY);
+  __uint(key_size, sizeof(u32));
+  __uint(value_size, sizeof(u32));
+} events SEC(".maps");
+
+SEC("uprobe")
+
+int BPF_KPROBE(uprobe_entry)
+{
+  u64 pid_tgid = bpf_get_current_pid_tgid();
+  u32 pid = pid_tgid >> 32;
+  u32 tid = (u32)pid_tgid;
+  u64 ts;
+
+  if (targ_pid && targ_pid != pid)
+    return 0;
+  if (targ_tgid && targ_tgid != tid)
+    return 0;
+
+  ts = bpf_ktime_get_ns();
+  bpf_map_update_elem(&starts, &tid, &ts, BPF_ANY);
+  return 0;
+}
+
+SEC("uretprobe")
+
+int BPF_KRETPROBE(uprobe_exit)
+{
+  u64 pid_tgid = bpf_get_current_pid_tgid();
+  u32 pid = pid_tgid >> 32;
+  u32 tid = (u32)pid_tgid;
+  struct event event = {};
+  u64 *tsp, ts, lat_us;
+
+  tsp = bpf_map_lookup_elem(&starts, &tid);
+  if (! tsp)
+    return 0; /* missed entry */
+
+  ts = bpf_ktime_get_ns();
+  lat_us = (ts - *tsp) / 1000;
+  bpf_map_delete_elem(&starts, &tid);
+  if (lat_us <= min_lat_us)
+    return 0;
+
+  event.lat_us = lat_us;
+  event.ts = ts;
+  event.pid = pid;
+  event.tid = tid;
+  bpf_get_current_comm(&event.task, sizeof(event.task));
+  bpf_perf_event_output(ctx, &events, BPF_F_CURRENT_CPU, &event, sizeof(event));
+  return 0;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -1 +1,7 @@
 pub const TASK_COMM_LEN: u8 = 20;
+pub const REQ_SYNC: u32 = 1 << 0;
+pub const REQ_META: u32 = 1 << 1;
+pub const REQ_FUA: u32 = 1 << 2;
+pub const REQ_PREFLUSH: u32 = 1 << 3;
+pub const REQ_RAHEAD: u32 = 1 << 4;
+pub const MAX_SLOTS: u8 = 27;
@@ -1,5 +1,5 @@
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Cfg {
     pub min_lat_us: u64,
     pub targ_reporting_interval_ms: u64,
@@ -9,4 +9,9 @@ pub struct Cfg {
     pub targ_filter_dev: bool,
     pub targ_filter_cgroup: bool,
     pub targ_filter_queued: bool,
+    pub targ_bio_op: Option<crate::bio_lat::BioOp>,
+    pub targ_uprobe: Option<crate::uprobe_lat::Uprobe>,
+    pub targ_func: Option<String>,
+    pub targ_stacks: bool,
+    pub targ_sample_freq_hz: u64,
 }
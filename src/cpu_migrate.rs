@@ -0,0 +1,79 @@
+mod skel {
+    include!(concat!(env!("OUT_DIR"), "/skel_cpu_migrate.rs"));
+}
+
+/// Number of times a process' threads moved between cpus in one reporting
+/// interval, and the most recent of those moves.
+#[derive(Debug, Clone, Copy)]
+pub struct Migrations {
+    pub count: u64,
+    pub orig_cpu: u32,
+    pub dest_cpu: u32,
+}
+
+impl std::fmt::Display for Migrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.count)
+    }
+}
+
+pub type Value = Migrations;
+pub struct CpuMigrate<'cls> {
+    // Need to hold this to keep the attached probes alive
+    skel: skel::CpuMigrateSkel<'cls>,
+    interval: std::time::Duration,
+    next_report: std::time::Instant,
+    pending: std::collections::VecDeque<crate::event::Event<Value>>,
+}
+unsafe impl plain::Plain for skel::cpu_migrate_types::migrations {}
+
+impl CpuMigrate<'_> {
+    fn collect(&mut self) -> Vec<crate::event::Event<Value>> {
+        let time = crate::time::now();
+        let maps = self.skel.maps();
+        let migrations = crate::stream::drain(maps.migrations());
+        let mut evs = Vec::with_capacity(migrations.len());
+        for (pid, data) in migrations {
+            let mut m = skel::cpu_migrate_types::migrations::default();
+            plain::copy_from_bytes(&mut m, &data).expect("Data buffer was too short");
+            let mut pid_bytes = [0u8; 4];
+            pid_bytes.copy_from_slice(&pid[..4]);
+            evs.push(crate::event::Event {
+                time,
+                task: m.task,
+                pid: u32::from_ne_bytes(pid_bytes),
+                value: Migrations {
+                    count: m.count,
+                    orig_cpu: m.orig_cpu,
+                    dest_cpu: m.dest_cpu,
+                },
+                stack: None,
+            });
+        }
+        evs
+    }
+}
+
+impl crate::tool::Tool for CpuMigrate<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        use crate::tool::Error;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::Skel;
+        use libbpf_rs::skel::SkelBuilder;
+        let skel_builder = skel::CpuMigrateSkelBuilder::default();
+        let mut open_skel = skel_builder.open().map_err(|_| Error::Libbpf)?;
+        open_skel.rodata_mut().targ_pid = cfg.targ_pid;
+        open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+        let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+        skel.attach().map_err(|_| Error::Libbpf)?;
+        let interval = std::time::Duration::from_millis(cfg.targ_reporting_interval_ms);
+        Ok(Self {
+            skel,
+            interval,
+            next_report: std::time::Instant::now() + interval,
+            pending: std::collections::VecDeque::new(),
+        })
+    }
+}
+
+crate::stream::impl_interval_stream_for!(CpuMigrate<'_>, Value);
@@ -0,0 +1,169 @@
+// Device numbers, as seen by the BPF programs, are encoded like the kernel's
+// `MKDEV`. Which is different from the `dev_t` we see in userspace.
+const MINORBITS: u32 = 20;
+const MINORMASK: u32 = (1 << MINORBITS) - 1;
+
+// Disks come and go (hotplug, device-mapper, loop devices...), so we re-read
+// their names when a device isn't known, or when what we know is this old.
+const MAX_AGE: std::time::Duration = std::time::Duration::from_secs(10);
+// But not more often than this, if we're asked about devices which don't exist.
+const MIN_AGE: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub fn mkdev(major: u32, minor: u32) -> u32 {
+    (major << MINORBITS) | (minor & MINORMASK)
+}
+
+pub fn major(dev: u32) -> u32 {
+    dev >> MINORBITS
+}
+
+pub fn minor(dev: u32) -> u32 {
+    dev & MINORMASK
+}
+
+// The userspace (glibc) encoding of `dev_t`
+fn from_dev_t(dev: u64) -> u32 {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    mkdev(major as u32, minor as u32)
+}
+
+struct Disks {
+    names: std::collections::HashMap<u32, String>,
+    devs: std::collections::HashMap<String, u32>,
+    read_at: std::time::Instant,
+}
+
+fn sys_dev_block_path(dev: u32) -> String {
+    format!("/sys/dev/block/{}:{}", major(dev), minor(dev))
+}
+
+// Device-mapper devices are named like "dm-0" by the kernel,
+// but they're known (and in /dev/mapper) by their dm name.
+fn read_dm_name(dev: u32) -> Option<String> {
+    std::fs::read_to_string(format!("{}/dm/name", sys_dev_block_path(dev)))
+        .ok()
+        .map(|name| name.trim_end().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+// Lines like "259        1  523264 nvme0n1p1", after a header
+fn read_proc_partitions() -> Vec<(u32, String)> {
+    let partitions = std::fs::read_to_string("/proc/partitions").unwrap_or_default();
+    partitions
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split_whitespace();
+            let major = cols.next()?.parse().ok()?;
+            let minor = cols.next()?.parse().ok()?;
+            let name = cols.nth(1)?.to_string();
+            Some((mkdev(major, minor), name))
+        })
+        .collect()
+}
+
+// Entries like "259:1" -> "../../devices/.../nvme0n1/nvme0n1p1"
+fn read_sys_dev_block() -> Vec<(u32, String)> {
+    let Ok(entries) = std::fs::read_dir("/sys/dev/block") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name();
+            let (major, minor) = file_name.to_str()?.split_once(':')?;
+            let target = std::fs::read_link(entry.path()).ok()?;
+            let name = target.file_name()?.to_str()?.to_string();
+            Some((mkdev(major.parse().ok()?, minor.parse().ok()?), name))
+        })
+        .collect()
+}
+
+impl Disks {
+    fn read() -> Self {
+        let mut names = std::collections::HashMap::new();
+        let mut devs = std::collections::HashMap::new();
+        for (dev, name) in read_proc_partitions()
+            .into_iter()
+            .chain(read_sys_dev_block())
+        {
+            if names.contains_key(&dev) {
+                continue;
+            }
+            devs.insert(name.clone(), dev);
+            let name = match read_dm_name(dev) {
+                Some(dm_name) => {
+                    devs.insert(dm_name.clone(), dev);
+                    dm_name
+                }
+                None => name,
+            };
+            names.insert(dev, name);
+        }
+        Self {
+            names,
+            devs,
+            read_at: std::time::Instant::now(),
+        }
+    }
+}
+
+static DISKS: std::sync::Mutex<Option<Disks>> = std::sync::Mutex::new(None);
+
+fn refreshed(disks: &mut Option<Disks>, missing: bool) -> &Disks {
+    let stale = match disks {
+        Some(d) => d.read_at.elapsed() > MAX_AGE || (missing && d.read_at.elapsed() > MIN_AGE),
+        None => true,
+    };
+    if stale {
+        *disks = Some(Disks::read());
+    }
+    disks.get_or_insert_with(Disks::read)
+}
+
+/// The name of a disk or partition, like "nvme0n1", "sda2" or a device-mapper
+/// name, from its device number (as encoded by the kernel's `MKDEV`).
+pub fn name(dev: u32) -> Option<String> {
+    let mut disks = DISKS.lock().ok()?;
+    let missing = !disks.as_ref().is_some_and(|d| d.names.contains_key(&dev));
+    refreshed(&mut disks, missing).names.get(&dev).cloned()
+}
+
+/// The name of a disk, or its "major:minor" numbers if it has no name.
+pub fn name_or_numbers(dev: u32) -> String {
+    name(dev).unwrap_or_else(|| format!("{}:{}", major(dev), minor(dev)))
+}
+
+/// The device number (as encoded by the kernel's `MKDEV`) of a disk or
+/// partition, from any of:
+/// - Its name, like "nvme0n1" or "sda2"
+/// - Its device-mapper name, like "vg0-root"
+/// - A path to its device file, like "/dev/sda" or "/dev/disk/by-label/root"
+/// - Its "major:minor" numbers, like "259:0"
+pub fn dev(disk: &str) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    if let Some((major, minor)) = disk.split_once(':') {
+        if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+            return Some(mkdev(major, minor));
+        }
+    }
+    if disk.starts_with('/') {
+        return Some(from_dev_t(std::fs::metadata(disk).ok()?.rdev()));
+    }
+    let mut disks = DISKS.lock().ok()?;
+    let missing = !disks.as_ref().is_some_and(|d| d.devs.contains_key(disk));
+    refreshed(&mut disks, missing).devs.get(disk).copied()
+}
+
+/// The whole disk which a partition is on, or the same device if it's a disk.
+/// Block i/o is traced on whole disks.
+pub fn whole_disk(dev: u32) -> u32 {
+    let path = sys_dev_block_path(dev);
+    if std::fs::metadata(format!("{path}/partition")).is_err() {
+        return dev;
+    }
+    std::fs::read_to_string(format!("{path}/../dev"))
+        .ok()
+        .and_then(|numbers| self::dev(numbers.trim_end()))
+        .unwrap_or(dev)
+}
@@ -1,13 +1,34 @@
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Event<Value> {
+    /// Since this program's start. When it happened, by its kernel timestamp, or
+    /// for monitoring tools, when it was reported. See `time::wall_clock()`.
     pub time: std::time::Duration,
     pub task: [u8; crate::bpf_constants::TASK_COMM_LEN as usize],
     pub pid: u32,
     pub value: Value,
+    /// Only for tools which take stacks, when `Cfg::targ_stacks` is set.
+    pub stack: Option<crate::stack::Stack>,
+}
+
+impl<Value> Event<Value> {
+    /// With its value mapped, e.g. into an enum, for events of several tools.
+    pub fn map<To>(self, f: impl FnOnce(Value) -> To) -> Event<To> {
+        Event {
+            time: self.time,
+            task: self.task,
+            pid: self.pid,
+            value: f(self.value),
+            stack: self.stack,
+        }
+    }
 }
 
 pub trait FromBytes<Value> {
     fn from_bytes(data: &[u8]) -> Event<Value>;
+    // For tools which take stacks
+    fn stack_ids(_data: &[u8]) -> Option<crate::stack::StackIds> {
+        None
+    }
 }
 
 pub(crate) fn pid_to_name(pid: i32) -> String {
@@ -17,6 +38,13 @@ pub(crate) fn pid_to_name(pid: i32) -> String {
         .to_string()
 }
 
+pub(crate) fn task_from_str(s: &str) -> [u8; crate::bpf_constants::TASK_COMM_LEN as usize] {
+    let mut task = [0; crate::bpf_constants::TASK_COMM_LEN as usize];
+    let len = std::cmp::min(task.len() - 1, s.len());
+    task[..len].copy_from_slice(&s.as_bytes()[..len]);
+    task
+}
+
 // An automatically-implemented "trait" for from_bytes in the typical case,
 // i.e. we have a custom c-event type from the BPF skeleton, but similar
 // conversion logic into a `crate::event::Event` struct. Can't (easily) be a
@@ -33,12 +61,37 @@ macro_rules! impl_from_bytes_for {
                 let mut event = <$CEvent>::default();
                 plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
                 $crate::event::Event {
-                    time: $crate::time::prog_start().elapsed(),
+                    time: $crate::time::since_prog_start(event.ts),
+                    task: event.task,
+                    pid: event.pid as u32,
+                    value: event.lat_us.into(),
+                    stack: None,
+                }
+            }
+        }
+    };
+    ($Prog:ty, $Value:ty, $CEvent:ty, with_stack_ids) => {
+        impl $crate::event::FromBytes<$Value> for $Prog {
+            fn from_bytes(data: &[u8]) -> $crate::event::Event<$Value> {
+                let mut event = <$CEvent>::default();
+                plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+                $crate::event::Event {
+                    time: $crate::time::since_prog_start(event.ts),
                     task: event.task,
                     pid: event.pid as u32,
                     value: event.lat_us.into(),
+                    stack: None,
                 }
             }
+            fn stack_ids(data: &[u8]) -> Option<$crate::stack::StackIds> {
+                let mut event = <$CEvent>::default();
+                plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+                Some($crate::stack::StackIds {
+                    pid: event.stack_pid,
+                    kernel: event.kern_stack_id,
+                    user: event.user_stack_id,
+                })
+            }
         }
     };
 }
@@ -1,7 +1,75 @@
 mod skel {
     include!(concat!(env!("OUT_DIR"), "/skel_fs_lat.rs"));
 }
-pub type Value = u64;
+
+/// The operation of a file system call, as numbered by the BPF program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsOp {
+    Read,
+    Write,
+    Open,
+    Fsync,
+    Other(u8),
+}
+
+impl From<u8> for FsOp {
+    fn from(op: u8) -> Self {
+        use FsOp::*;
+        match op {
+            0 => Read,
+            1 => Write,
+            2 => Open,
+            3 => Fsync,
+            op => Other(op),
+        }
+    }
+}
+
+impl From<FsOp> for u8 {
+    fn from(op: FsOp) -> Self {
+        use FsOp::*;
+        match op {
+            Read => 0,
+            Write => 1,
+            Open => 2,
+            Fsync => 3,
+            Other(op) => op,
+        }
+    }
+}
+
+impl std::fmt::Display for FsOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use FsOp::*;
+        match self {
+            Read => write!(f, "READ"),
+            Write => write!(f, "WRITE"),
+            Open => write!(f, "OPEN"),
+            Fsync => write!(f, "FSYNC"),
+            Other(op) => write!(f, "OP_{op}"),
+        }
+    }
+}
+
+/// A completed file system call. The file is only its name, without its
+/// directory, and cut short to 31 bytes. The size is what was read or written
+/// (negative on errors), or for syncs, the length of the range synced.
+#[derive(Debug, Clone)]
+pub struct FsCall {
+    pub lat_us: u64,
+    pub file: String,
+    pub op: FsOp,
+    pub size: i64,
+    pub offset: i64,
+}
+
+impl std::fmt::Display for FsCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.lat_us)
+    }
+}
+
+pub type Value = FsCall;
 pub struct FsLat<'cls> {
     // Need to hold this to keep the attached probes alive
     _skel: skel::FsLatSkel<'cls>,
@@ -9,6 +77,38 @@ pub struct FsLat<'cls> {
     rx: std::sync::mpsc::Receiver<crate::event::Event<Value>>,
 }
 unsafe impl plain::Plain for skel::fs_lat_types::event {}
-crate::event::impl_from_bytes_for!(FsLat<'_>, Value, skel::fs_lat_types::event);
+
+impl crate::event::FromBytes<Value> for FsLat<'_> {
+    fn from_bytes(data: &[u8]) -> crate::event::Event<Value> {
+        let mut event = skel::fs_lat_types::event::default();
+        plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+        let file = event.file.split(|c| *c == 0).next().unwrap_or_default();
+        crate::event::Event {
+            time: crate::time::since_prog_start(event.ts),
+            task: event.task,
+            pid: event.pid as u32,
+            value: FsCall {
+                lat_us: event.lat_us,
+                file: String::from_utf8_lossy(file).into_owned(),
+                op: FsOp::from(event.op),
+                size: event.size,
+                offset: event.offset,
+            },
+            stack: None,
+        }
+    }
+    fn stack_ids(data: &[u8]) -> Option<crate::stack::StackIds> {
+        let mut event = skel::fs_lat_types::event::default();
+        plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+        Some(crate::stack::StackIds {
+            pid: event.stack_pid,
+            kernel: event.kern_stack_id,
+            user: event.user_stack_id,
+        })
+    }
+}
+
 crate::stream::impl_stream_for!(FsLat<'_>, Value);
-crate::tool::impl_tool_for_perf_event_bpf_prog!(FsLat, skel::FsLatSkelBuilder);
+crate::tool::impl_tool_for_perf_event_bpf_prog!(FsLat, skel::FsLatSkelBuilder, |open_skel, cfg| {
+    open_skel.rodata_mut().targ_stacks = cfg.targ_stacks;
+});
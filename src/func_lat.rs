@@ -0,0 +1,310 @@
+mod skel {
+    include!(concat!(env!("OUT_DIR"), "/skel_func_lat.rs"));
+}
+
+use crate::bpf_constants::MAX_SLOTS;
+
+// Each function takes two kprobes, which are slow to attach (and detach)
+const MAX_FUNCS: usize = 1000;
+
+/// A call of a kernel function.
+#[derive(Debug, Clone)]
+pub struct FuncCall {
+    pub func: String,
+    pub lat_us: u64,
+}
+
+impl std::fmt::Display for FuncCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.lat_us)
+    }
+}
+
+/// Calls of a kernel function in one reporting interval, by their latency.
+/// Slot `n` counts the calls which took [2^n, 2^(n+1)) microseconds, except
+/// for the first slot, which includes 0, and the last, which includes longer.
+#[derive(Debug, Clone)]
+pub struct FuncHist {
+    pub func: String,
+    pub slots: [u64; MAX_SLOTS as usize],
+}
+
+impl FuncHist {
+    pub fn count(&self) -> u64 {
+        self.slots.iter().sum()
+    }
+
+    /// The latencies, in microseconds, of calls counted in a slot.
+    pub fn slot_range(slot: usize) -> std::ops::Range<u64> {
+        let lo = if slot == 0 { 0 } else { 1 << slot };
+        lo..(1 << (slot + 1))
+    }
+}
+
+impl std::fmt::Display for FuncHist {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.count())
+    }
+}
+
+// Shell-style, with '*' and '?'
+fn glob_match(glob: &[u8], s: &[u8]) -> bool {
+    match (glob.split_first(), s.split_first()) {
+        (None, _) => s.is_empty(),
+        (Some((b'*', rest)), _) => {
+            glob_match(rest, s) || (!s.is_empty() && glob_match(glob, &s[1..]))
+        }
+        (Some((b'?', rest)), Some((_, s_rest))) => glob_match(rest, s_rest),
+        (Some((g, rest)), Some((c, s_rest))) => g == c && glob_match(rest, s_rest),
+        (Some(_), None) => false,
+    }
+}
+
+// Not every function in the kernel can be traced, some are "notrace".
+// Lines like "vfs_read" or "nvme_poll [nvme]".
+fn traceable_funcs() -> Option<std::collections::HashSet<String>> {
+    ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+        .iter()
+        .find_map(|dir| std::fs::read_to_string(format!("{dir}/available_filter_functions")).ok())
+        .map(|funcs| {
+            funcs
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string)
+                .collect()
+        })
+}
+
+// Kernel functions matching the `targ_func` glob, and their symbols
+fn matching_funcs(
+    cfg: &crate::cfg::Cfg,
+) -> Result<(Vec<String>, crate::ksyms::Ksyms), crate::tool::Error> {
+    use crate::tool::Error;
+    let glob = cfg
+        .targ_func
+        .as_deref()
+        .ok_or(Error::Misconfig("A kernel function (or glob) is required"))?;
+    let traceable = traceable_funcs();
+    let mut ksyms = crate::ksyms::Ksyms::load();
+    ksyms.retain(|sym| {
+        glob_match(glob.as_bytes(), sym.name.as_bytes())
+            && traceable.as_ref().is_none_or(|t| t.contains(&sym.name))
+    });
+    if ksyms.is_empty() {
+        return Err(Error::Misconfig("No traceable kernel functions match"));
+    }
+    let mut funcs: Vec<String> = ksyms.iter().map(|sym| sym.name.clone()).collect();
+    funcs.sort();
+    funcs.dedup();
+    if funcs.len() > MAX_FUNCS {
+        return Err(Error::Misconfig("Too many kernel functions match"));
+    }
+    Ok((funcs, ksyms))
+}
+
+fn open<'cls>(
+    cfg: &crate::cfg::Cfg,
+    hist: bool,
+) -> Result<skel::OpenFuncLatSkel<'cls>, crate::tool::Error> {
+    use crate::tool::Error;
+    use libbpf_rs::skel::SkelBuilder;
+    let skel_builder = skel::FuncLatSkelBuilder::default();
+    let mut open_skel = skel_builder.open().map_err(|_| Error::Libbpf)?;
+    open_skel.rodata_mut().min_lat_us = cfg.min_lat_us;
+    open_skel.rodata_mut().targ_pid = cfg.targ_pid;
+    open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+    open_skel.rodata_mut().targ_hist = hist;
+    Ok(open_skel)
+}
+
+// Preferred, because they're cheaper than kprobes. But a program can only be
+// attached to one function, and only if the kernel has BTF for it.
+fn attach_fentry<'cls>(
+    cfg: &crate::cfg::Cfg,
+    hist: bool,
+    func: &str,
+) -> Result<(skel::FuncLatSkel<'cls>, Vec<libbpf_rs::Link>), crate::tool::Error> {
+    use crate::tool::Error;
+    use libbpf_rs::skel::OpenSkel;
+    let mut open_skel = open(cfg, hist)?;
+    let mut progs = open_skel.progs_mut();
+    let target = Some(func.to_string());
+    let e = |_| Error::Libbpf;
+    progs
+        .fentry_entry()
+        .set_attach_target(0, target.clone())
+        .map_err(e)?;
+    progs.fexit_exit().set_attach_target(0, target).map_err(e)?;
+    progs.kprobe_entry().set_autoload(false).map_err(e)?;
+    progs.kprobe_exit().set_autoload(false).map_err(e)?;
+    let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+    let links = vec![
+        skel.progs_mut()
+            .fentry_entry()
+            .attach_trace()
+            .map_err(|_| Error::Libbpf)?,
+        skel.progs_mut()
+            .fexit_exit()
+            .attach_trace()
+            .map_err(|_| Error::Libbpf)?,
+    ];
+    Ok((skel, links))
+}
+
+fn attach_kprobes<'cls>(
+    cfg: &crate::cfg::Cfg,
+    hist: bool,
+    funcs: &[String],
+) -> Result<(skel::FuncLatSkel<'cls>, Vec<libbpf_rs::Link>), crate::tool::Error> {
+    use crate::tool::Error;
+    use libbpf_rs::skel::OpenSkel;
+    let mut open_skel = open(cfg, hist)?;
+    let mut progs = open_skel.progs_mut();
+    let e = |_| Error::Libbpf;
+    progs.fentry_entry().set_autoload(false).map_err(e)?;
+    progs.fexit_exit().set_autoload(false).map_err(e)?;
+    let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+    let mut links = Vec::with_capacity(funcs.len() * 2);
+    for func in funcs {
+        // Some functions are inlined in places, or have ambiguous names
+        let entry = skel.progs_mut().kprobe_entry().attach_kprobe(false, func);
+        let exit = skel.progs_mut().kprobe_exit().attach_kprobe(true, func);
+        match (entry, exit) {
+            (Ok(entry), Ok(exit)) => links.extend([entry, exit]),
+            (Err(e), _) | (_, Err(e)) => log::warn!("Failed to attach to {func}: {e}"),
+        }
+    }
+    if links.is_empty() {
+        return Err(Error::Libbpf);
+    }
+    Ok((skel, links))
+}
+
+fn attach<'cls>(
+    cfg: &crate::cfg::Cfg,
+    hist: bool,
+) -> Result<
+    (
+        skel::FuncLatSkel<'cls>,
+        Vec<libbpf_rs::Link>,
+        crate::ksyms::Ksyms,
+    ),
+    crate::tool::Error,
+> {
+    let (funcs, ksyms) = matching_funcs(cfg)?;
+    if let [func] = funcs.as_slice() {
+        match attach_fentry(cfg, hist, func) {
+            Ok((skel, links)) => return Ok((skel, links, ksyms)),
+            Err(_) => log::info!("Can't use fentry/fexit for {func}, falling back to kprobes"),
+        }
+    }
+    let (skel, links) = attach_kprobes(cfg, hist, &funcs)?;
+    Ok((skel, links, ksyms))
+}
+
+fn func_name(ksyms: &crate::ksyms::Ksyms, ip: u64) -> String {
+    match ksyms.resolve(ip) {
+        Some(sym) => sym.name.clone(),
+        None => format!("{ip:#x}"),
+    }
+}
+
+pub type Value = FuncCall;
+pub struct FuncLat<'cls> {
+    // Need to hold these to keep the attached probes alive
+    _skel: skel::FuncLatSkel<'cls>,
+    _links: Vec<libbpf_rs::Link>,
+    ev_buf: libbpf_rs::PerfBuffer<'cls>,
+    rx: std::sync::mpsc::Receiver<crate::event::Event<Value>>,
+}
+unsafe impl plain::Plain for skel::func_lat_types::event {}
+unsafe impl plain::Plain for skel::func_lat_types::hist {}
+crate::stream::impl_stream_for!(FuncLat<'_>, Value);
+
+impl crate::tool::Tool for FuncLat<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        use crate::tool::Error;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (skel, links, ksyms) = attach(&cfg, false)?;
+        let ev_buf = libbpf_rs::PerfBufferBuilder::new(skel.maps().events())
+            .sample_cb(move |_cpu, data| {
+                let mut event = skel::func_lat_types::event::default();
+                plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+                tx.send(crate::event::Event {
+                    time: crate::time::since_prog_start(event.ts),
+                    task: event.task,
+                    pid: event.pid as u32,
+                    value: FuncCall {
+                        func: func_name(&ksyms, event.ip),
+                        lat_us: event.lat_us,
+                    },
+                    stack: None,
+                })
+                .unwrap();
+            })
+            .build()
+            .map_err(|_| Error::Libbpf)?;
+        Ok(Self {
+            _skel: skel,
+            _links: links,
+            ev_buf,
+            rx,
+        })
+    }
+}
+
+pub struct FuncLatHist<'cls> {
+    // Need to hold these to keep the attached probes alive
+    skel: skel::FuncLatSkel<'cls>,
+    _links: Vec<libbpf_rs::Link>,
+    ksyms: crate::ksyms::Ksyms,
+    pid: u32,
+    interval: std::time::Duration,
+    next_report: std::time::Instant,
+    pending: std::collections::VecDeque<crate::event::Event<FuncHist>>,
+}
+
+impl FuncLatHist<'_> {
+    fn collect(&mut self) -> Vec<crate::event::Event<FuncHist>> {
+        let time = crate::time::now();
+        let maps = self.skel.maps();
+        let hists = crate::stream::drain(maps.hists());
+        let mut evs = Vec::with_capacity(hists.len());
+        for (ip, data) in hists {
+            let mut h = skel::func_lat_types::hist::default();
+            plain::copy_from_bytes(&mut h, &data).expect("Data buffer was too short");
+            let mut ip_bytes = [0u8; 8];
+            ip_bytes.copy_from_slice(&ip[..8]);
+            let func = func_name(&self.ksyms, u64::from_ne_bytes(ip_bytes));
+            evs.push(crate::event::Event {
+                time,
+                task: crate::event::task_from_str(&func),
+                pid: self.pid,
+                value: FuncHist {
+                    func,
+                    slots: h.slots,
+                },
+                stack: None,
+            });
+        }
+        evs
+    }
+}
+
+impl crate::tool::Tool for FuncLatHist<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        let (skel, links, ksyms) = attach(&cfg, true)?;
+        let interval = std::time::Duration::from_millis(cfg.targ_reporting_interval_ms);
+        Ok(Self {
+            skel,
+            _links: links,
+            ksyms,
+            pid: cfg.targ_pid as u32,
+            interval,
+            next_report: std::time::Instant::now() + interval,
+            pending: std::collections::VecDeque::new(),
+        })
+    }
+}
+
+crate::stream::impl_interval_stream_for!(FuncLatHist<'_>, FuncHist);
@@ -0,0 +1,54 @@
+// Kernel symbols, from /proc/kallsyms. Their addresses are only shown to root,
+// which we need to be anyway, to load the BPF programs.
+pub(crate) struct Ksym {
+    pub addr: u64,
+    pub name: String,
+}
+
+pub(crate) struct Ksyms {
+    // Sorted by address
+    syms: Vec<Ksym>,
+}
+
+impl Ksyms {
+    /// Function (text) symbols, of the kernel and its modules.
+    pub fn load() -> Self {
+        let kallsyms = std::fs::read_to_string("/proc/kallsyms").unwrap_or_default();
+        // Lines like "ffffffff8a2f3f10 T vfs_read" or "... t nvme_poll\t[nvme]"
+        let mut syms: Vec<Ksym> = kallsyms
+            .lines()
+            .filter_map(|line| {
+                let mut cols = line.split_whitespace();
+                let addr = u64::from_str_radix(cols.next()?, 16).ok()?;
+                let kind = cols.next()?;
+                let name = cols.next()?.to_string();
+                match kind {
+                    "t" | "T" | "w" | "W" if addr != 0 => Some(Ksym { addr, name }),
+                    _ => None,
+                }
+            })
+            .collect();
+        syms.sort_by_key(|sym| sym.addr);
+        Self { syms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.syms.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ksym> {
+        self.syms.iter()
+    }
+
+    pub fn retain(&mut self, f: impl FnMut(&Ksym) -> bool) {
+        self.syms.retain(f);
+    }
+
+    /// The symbol an address is in, i.e. the nearest symbol at or below it.
+    pub fn resolve(&self, addr: u64) -> Option<&Ksym> {
+        match self.syms.partition_point(|sym| sym.addr <= addr) {
+            0 => None,
+            i => Some(&self.syms[i - 1]),
+        }
+    }
+}
@@ -1,26 +1,60 @@
 mod bpf_constants;
 mod cfg;
 mod event;
+mod ksyms;
+mod merge;
+mod perf_event;
 mod rlimit;
+mod stack;
 mod stream;
 
 mod bio_lat;
+mod bio_stat;
+mod cpu_migrate;
 mod cpu_pct;
 mod fs_lat;
+mod func_lat;
 mod mem_pct;
+mod profile;
 mod rq_lat;
+mod runq_len;
 mod tcp_pkt_lat;
+mod uprobe_lat;
 
+pub mod disk;
 pub mod time;
 pub mod tool;
 
 pub use cfg::Cfg;
 pub use event::Event;
+pub use merge::Merge;
+pub use merge::Timed;
 pub use rlimit::must_bump_memlock_rlimit_once;
+pub use stack::Frame;
+pub use stack::Stack;
 
+pub use bio_lat::Bio;
+pub use bio_lat::BioFlags;
 pub use bio_lat::BioLat;
+pub use bio_lat::BioOp;
+pub use bio_stat::BioStat;
+pub use bio_stat::DiskStat;
+pub use cpu_migrate::CpuMigrate;
+pub use cpu_migrate::Migrations;
 pub use cpu_pct::CpuPct;
+pub use fs_lat::FsCall;
 pub use fs_lat::FsLat;
+pub use fs_lat::FsOp;
+pub use func_lat::FuncCall;
+pub use func_lat::FuncHist;
+pub use func_lat::FuncLat;
+pub use func_lat::FuncLatHist;
 pub use mem_pct::MemPct;
+pub use profile::Profile;
 pub use rq_lat::RqLat;
+pub use runq_len::QueueLen;
+pub use runq_len::RunqLen;
+pub use tcp_pkt_lat::TcpPkt;
 pub use tcp_pkt_lat::TcpPktLat;
+pub use uprobe_lat::Uprobe;
+pub use uprobe_lat::UprobeLat;
@@ -49,7 +49,7 @@ impl crate::tool::Tool for MemPct {
     fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
         let (tx, rx) = std::sync::mpsc::channel();
         Ok(Self {
-            cfg,
+            cfg: cfg.clone(),
             rx,
             task: crate::event::pid_to_name(cfg.targ_pid),
             _collector_task: spawn_collector(tx, cfg),
@@ -70,10 +70,11 @@ impl futures::Stream for MemPct {
                 task[..std::cmp::min(16, self.task.len())]
                     .copy_from_slice(&self.task.as_bytes()[..std::cmp::min(16, self.task.len())]);
                 let ev = crate::event::Event {
-                    time: crate::time::prog_start().elapsed(),
+                    time: crate::time::now(),
                     task,
                     pid: self.cfg.targ_pid as u32,
                     value: format!("{:00.02}", ev),
+                    stack: None,
                 };
                 std::task::Poll::Ready(Some(ev))
             }
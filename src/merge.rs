@@ -0,0 +1,145 @@
+/// Anything with a time since this program's start, like an `Event`, to be
+/// merged in order of those times.
+pub trait Timed {
+    fn time(&self) -> std::time::Duration;
+}
+
+impl<Value> Timed for crate::event::Event<Value> {
+    fn time(&self) -> std::time::Duration {
+        self.time
+    }
+}
+
+// Earliest first (as the heap is a max-heap), then in the order they came
+struct Held<Item> {
+    time: std::time::Duration,
+    seq: u64,
+    item: Item,
+}
+
+impl<Item> PartialEq for Held<Item> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.time, self.seq) == (other.time, other.seq)
+    }
+}
+
+impl<Item> Eq for Held<Item> {}
+
+impl<Item> PartialOrd for Held<Item> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Item> Ord for Held<Item> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (other.time, other.seq).cmp(&(self.time, self.seq))
+    }
+}
+
+/// The events of several streams (e.g. of tools, mapped with `Event::map` and
+/// boxed, to be of one type), in order of their times. Each is held until it's
+/// `window` old, by the kernel's clock, in case an earlier one is yet to come,
+/// as events are received some time after they happen, and tools' events are
+/// received independently. Events later than that are passed on as they come.
+/// Ends when every stream has, after passing on what's held.
+pub struct Merge<S: futures::Stream> {
+    // None once ended
+    streams: Vec<Option<S>>,
+    held: std::collections::BinaryHeap<Held<S::Item>>,
+    window: std::time::Duration,
+    seq: u64,
+    // Until the earliest is due, once there's one to wait for
+    sleep: Option<std::pin::Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<S: futures::Stream> Merge<S> {
+    pub fn new(streams: impl IntoIterator<Item = S>, window: std::time::Duration) -> Self {
+        Self {
+            streams: streams.into_iter().map(Some).collect(),
+            held: std::collections::BinaryHeap::new(),
+            window,
+            seq: 0,
+            sleep: None,
+        }
+    }
+}
+
+impl<S> Merge<S>
+where
+    S: futures::Stream + Unpin,
+    S::Item: Timed,
+{
+    // Holds what the streams have ready
+    fn receive(&mut self, ctx: &mut std::task::Context) {
+        use futures::StreamExt;
+        for stream in self.streams.iter_mut() {
+            while let Some(s) = stream {
+                match s.poll_next_unpin(ctx) {
+                    std::task::Poll::Ready(Some(item)) => {
+                        let time = item.time();
+                        self.held.push(Held {
+                            time,
+                            seq: self.seq,
+                            item,
+                        });
+                        self.seq += 1;
+                    }
+                    std::task::Poll::Ready(None) => *stream = None,
+                    std::task::Poll::Pending => break,
+                }
+            }
+        }
+    }
+
+    /// What's held, and what the streams have ready, in order, without waiting,
+    /// e.g. when stopping before the streams have ended.
+    pub fn drain(mut self) -> impl Iterator<Item = S::Item> {
+        let mut ctx = std::task::Context::from_waker(futures::task::noop_waker_ref());
+        self.receive(&mut ctx);
+        std::iter::from_fn(move || self.held.pop().map(|held| held.item))
+    }
+}
+
+// Held events are never pinned
+impl<S: futures::Stream + Unpin> Unpin for Merge<S> {}
+
+impl<S> futures::Stream for Merge<S>
+where
+    S: futures::Stream + Unpin,
+    S::Item: Timed,
+{
+    type Item = S::Item;
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        ctx: &mut std::task::Context,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::future::Future;
+        let this = self.get_mut();
+        this.receive(ctx);
+        let ended = this.streams.iter().all(Option::is_none);
+        let Some(earliest) = this.held.peek() else {
+            return match ended {
+                true => std::task::Poll::Ready(None),
+                false => std::task::Poll::Pending,
+            };
+        };
+        let now = crate::time::now();
+        let due = earliest.time + this.window;
+        if ended || now >= due {
+            return std::task::Poll::Ready(this.held.pop().map(|held| held.item));
+        }
+        // One timer, reset as the earliest changes
+        let deadline = tokio::time::Instant::now() + (due - now);
+        let sleep = this
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+        sleep.as_mut().reset(deadline);
+        match sleep.as_mut().poll(ctx) {
+            std::task::Poll::Ready(()) => {
+                std::task::Poll::Ready(this.held.pop().map(|held| held.item))
+            }
+            std::task::Poll::Pending => std::task::Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// The leading, stable part of `struct perf_event_attr` (PERF_ATTR_SIZE_VER0),
+// which is all we need to set up a sampling timer. The libc crate doesn't
+// define this struct for us.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_freq: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+const PERF_TYPE_SOFTWARE: u32 = 1;
+const PERF_COUNT_SW_CPU_CLOCK: u64 = 0;
+const PERF_ATTR_FLAG_FREQ: u64 = 1 << 10;
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+fn open_cpu_clock(cpu: i32, freq_hz: u64) -> Result<i32, std::io::Error> {
+    let attr = PerfEventAttr {
+        type_: PERF_TYPE_SOFTWARE,
+        size: std::mem::size_of::<PerfEventAttr>() as u32,
+        config: PERF_COUNT_SW_CPU_CLOCK,
+        sample_freq: freq_hz,
+        flags: PERF_ATTR_FLAG_FREQ,
+        ..Default::default()
+    };
+    let pid: libc::pid_t = -1;
+    let group_fd: libc::c_int = -1;
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr as *const PerfEventAttr,
+            pid,
+            cpu,
+            group_fd,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    match fd {
+        -1 => Err(std::io::Error::last_os_error()),
+        fd => Ok(fd as i32),
+    }
+}
+
+// Attaches `prog` to a cpu clock on every (online) cpu, sampling at `freq_hz`.
+// Libbpf takes ownership of the perf event fds, closing them when the links
+// are dropped.
+pub(crate) fn attach_cpu_clock_sampler(
+    prog: &mut libbpf_rs::Program,
+    freq_hz: u64,
+) -> Result<Vec<libbpf_rs::Link>, crate::tool::Error> {
+    use crate::tool::Error;
+    let mut links = Vec::new();
+    let ncpus = libbpf_rs::num_possible_cpus().map_err(|_| Error::Libbpf)?;
+    for cpu in 0..ncpus as i32 {
+        match open_cpu_clock(cpu, freq_hz) {
+            Ok(fd) => match prog.attach_perf_event(fd) {
+                Ok(link) => links.push(link),
+                Err(_) => {
+                    unsafe { libc::close(fd) };
+                    return Err(Error::Libbpf);
+                }
+            },
+            // Possible, but offline, cpus can't be sampled
+            Err(e) if e.raw_os_error() == Some(libc::ENODEV) => continue,
+            Err(e) => {
+                log::error!("Failed to open perf event on cpu {cpu}: {e}");
+                return Err(Error::Runtime("Failed to open perf event"));
+            }
+        }
+    }
+    Ok(links)
+}
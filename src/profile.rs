@@ -0,0 +1,81 @@
+mod skel {
+    include!(concat!(env!("OUT_DIR"), "/skel_profile.rs"));
+}
+
+/// The number of times a stack was sampled on a cpu in one reporting interval.
+/// The stack is the event's.
+pub type Value = u64;
+pub struct Profile<'cls> {
+    skel: skel::ProfileSkel<'cls>,
+    // Need to hold these to keep the samplers alive
+    _links: Vec<libbpf_rs::Link>,
+    stacks: crate::stack::Stacks,
+    interval: std::time::Duration,
+    next_report: std::time::Instant,
+    pending: std::collections::VecDeque<crate::event::Event<Value>>,
+}
+unsafe impl plain::Plain for skel::profile_types::profile_key {}
+
+impl Profile<'_> {
+    fn collect(&mut self) -> Vec<crate::event::Event<Value>> {
+        let time = crate::time::now();
+        let maps = self.skel.maps();
+        let counts = crate::stream::drain(maps.counts());
+        let mut evs = Vec::with_capacity(counts.len());
+        let mut seen = Vec::with_capacity(counts.len());
+        for (key, data) in counts {
+            let mut k = skel::profile_types::profile_key::default();
+            plain::copy_from_bytes(&mut k, &key).expect("Data buffer was too short");
+            let mut count = [0u8; 8];
+            count.copy_from_slice(&data[..8]);
+            let ids = crate::stack::StackIds {
+                pid: k.pid,
+                kernel: k.kern_stack_id,
+                user: k.user_stack_id,
+            };
+            seen.push(ids);
+            evs.push(crate::event::Event {
+                time,
+                task: k.task,
+                pid: k.pid,
+                value: u64::from_ne_bytes(count),
+                stack: Some(self.stacks.resolve(ids)),
+            });
+        }
+        // Only after they've all been resolved, many samples share a stack
+        for ids in seen {
+            self.stacks.forget(ids);
+        }
+        evs
+    }
+}
+
+impl crate::tool::Tool for Profile<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        use crate::tool::Error;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::Skel;
+        use libbpf_rs::skel::SkelBuilder;
+        let skel_builder = skel::ProfileSkelBuilder::default();
+        let mut open_skel = skel_builder.open().map_err(|_| Error::Libbpf)?;
+        open_skel.rodata_mut().targ_pid = cfg.targ_pid;
+        open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+        let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+        let links = crate::perf_event::attach_cpu_clock_sampler(
+            skel.progs_mut().do_sample(),
+            cfg.targ_sample_freq_hz,
+        )?;
+        let stacks = crate::stack::Stacks::from_object(skel.object())?.ok_or(Error::Libbpf)?;
+        let interval = std::time::Duration::from_millis(cfg.targ_reporting_interval_ms);
+        Ok(Self {
+            skel,
+            _links: links,
+            stacks,
+            interval,
+            next_report: std::time::Instant::now() + interval,
+            pending: std::collections::VecDeque::new(),
+        })
+    }
+}
+
+crate::stream::impl_interval_stream_for!(Profile<'_>, Value);
@@ -9,6 +9,8 @@ pub struct RqLat<'cls> {
     rx: std::sync::mpsc::Receiver<crate::event::Event<Value>>,
 }
 unsafe impl plain::Plain for skel::rq_lat_types::event {}
-crate::event::impl_from_bytes_for!(RqLat<'_>, Value, skel::rq_lat_types::event);
+crate::event::impl_from_bytes_for!(RqLat<'_>, Value, skel::rq_lat_types::event, with_stack_ids);
 crate::stream::impl_stream_for!(RqLat<'_>, Value);
-crate::tool::impl_tool_for_perf_event_bpf_prog!(RqLat, skel::RqLatSkelBuilder);
+crate::tool::impl_tool_for_perf_event_bpf_prog!(RqLat, skel::RqLatSkelBuilder, |open_skel, cfg| {
+    open_skel.rodata_mut().targ_stacks = cfg.targ_stacks;
+});
@@ -0,0 +1,96 @@
+mod skel {
+    include!(concat!(env!("OUT_DIR"), "/skel_runq_len.rs"));
+}
+
+// Odd, so that we don't sample in lockstep with other periodic activity
+const SAMPLE_FREQ_HZ: u64 = 99;
+
+/// Run queue length on a cpu, sampled over one reporting interval.
+/// The task running on the cpu when sampled is not counted.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueLen {
+    pub cpu: u32,
+    pub avg: f32,
+    pub max: u64,
+}
+
+impl std::fmt::Display for QueueLen {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:00.02}", self.avg)
+    }
+}
+
+pub type Value = QueueLen;
+pub struct RunqLen<'cls> {
+    skel: skel::RunqLenSkel<'cls>,
+    // Need to hold these to keep the samplers alive
+    _links: Vec<libbpf_rs::Link>,
+    interval: std::time::Duration,
+    next_report: std::time::Instant,
+    pending: std::collections::VecDeque<crate::event::Event<Value>>,
+}
+unsafe impl plain::Plain for skel::runq_len_types::runq_len {}
+
+impl RunqLen<'_> {
+    fn collect(&mut self) -> Vec<crate::event::Event<Value>> {
+        let time = crate::time::now();
+        let maps = self.skel.maps();
+        let mut evs = Vec::new();
+        let ncpus = libbpf_rs::num_possible_cpus().unwrap_or(0) as u32;
+        for cpu in 0..ncpus {
+            let key = cpu.to_ne_bytes();
+            let mut len = skel::runq_len_types::runq_len::default();
+            // In one step, so that no samples are taken between reading and clearing
+            match maps.runq_lens().lookup_and_delete(&key) {
+                Ok(Some(data)) => {
+                    plain::copy_from_bytes(&mut len, &data).expect("Data buffer was too short")
+                }
+                Ok(None) => continue,
+                Err(e) => {
+                    log::error!("Error reading run queue lengths: {:?}", e);
+                    break;
+                }
+            }
+            if len.samples == 0 {
+                continue;
+            }
+            evs.push(crate::event::Event {
+                time,
+                task: crate::event::task_from_str(&format!("cpu{cpu}")),
+                pid: 0,
+                value: QueueLen {
+                    cpu,
+                    avg: len.sum as f32 / len.samples as f32,
+                    max: len.max,
+                },
+                stack: None,
+            });
+        }
+        evs
+    }
+}
+
+impl crate::tool::Tool for RunqLen<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        use crate::tool::Error;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::SkelBuilder;
+        let skel_builder = skel::RunqLenSkelBuilder::default();
+        let open_skel = skel_builder.open().map_err(|_| Error::Libbpf)?;
+        let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+        let links = crate::perf_event::attach_cpu_clock_sampler(
+            skel.progs_mut().do_sample(),
+            SAMPLE_FREQ_HZ,
+        )?;
+        let interval = std::time::Duration::from_millis(cfg.targ_reporting_interval_ms);
+        Ok(Self {
+            skel,
+            _links: links,
+            interval,
+            next_report: std::time::Instant::now() + interval,
+            pending: std::collections::VecDeque::new(),
+        })
+    }
+}
+
+crate::stream::impl_interval_stream_for!(RunqLen<'_>, Value);
@@ -0,0 +1,279 @@
+/// A stack trace, innermost frame first. Either may be empty, e.g. kernel
+/// threads have no user stack.
+#[derive(Debug, Clone, Default)]
+pub struct Stack {
+    pub kernel: Vec<Frame>,
+    pub user: Vec<Frame>,
+}
+
+/// A frame of a stack trace. Symbolized if its address was found in the
+/// kernel's symbols, or in the symbols of a binary or library (`object`).
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub addr: u64,
+    pub symbol: Option<String>,
+    pub offset: u64,
+    pub object: Option<String>,
+}
+
+impl Stack {
+    /// In the "folded" format of flame graph tools, like "task;main;read;vfs_read_[k]",
+    /// i.e. outermost frame first, with kernel frames suffixed by "_[k]".
+    pub fn folded(&self, task: &str) -> String {
+        let mut folded = task.replace([';', ' '], "_");
+        for frame in self.user.iter().rev() {
+            folded.push(';');
+            folded.push_str(&frame.name());
+        }
+        for frame in self.kernel.iter().rev() {
+            folded.push(';');
+            folded.push_str(&frame.name());
+            folded.push_str("_[k]");
+        }
+        folded
+    }
+}
+
+impl Frame {
+    /// The frame's symbol, or its address if it has none.
+    pub fn name(&self) -> String {
+        match &self.symbol {
+            Some(symbol) => symbol.clone(),
+            None => format!("{:#x}", self.addr),
+        }
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{symbol}+{:#x}", self.offset)?,
+            None => write!(f, "{:#x}", self.addr)?,
+        }
+        match &self.object {
+            Some(object) => write!(f, " ({object})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where to find a stack, and the process it was taken in, from a BPF event.
+/// Negative ids are stacks which couldn't be taken.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StackIds {
+    pub pid: u32,
+    pub kernel: i32,
+    pub user: i32,
+}
+
+// Function symbols in an ELF file, and where its loadable segments are in the
+// file, to translate the offsets of mapped addresses into symbol addresses.
+struct ElfSyms {
+    // (file offset, address, size)
+    segments: Vec<(u64, u64, u64)>,
+    // (address, size, name), sorted by address
+    syms: Vec<(u64, u64, String)>,
+}
+
+impl ElfSyms {
+    fn read(path: &str) -> Option<Self> {
+        use object::Object;
+        use object::ObjectSegment;
+        use object::ObjectSymbol;
+        let data = std::fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let segments = file
+            .segments()
+            .map(|seg| (seg.file_range().0, seg.address(), seg.file_range().1))
+            .collect();
+        let mut syms: Vec<(u64, u64, String)> = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|sym| sym.kind() == object::SymbolKind::Text && sym.address() != 0)
+            .filter_map(|sym| Some((sym.address(), sym.size(), sym.name().ok()?.to_string())))
+            .collect();
+        syms.sort();
+        syms.dedup_by_key(|(addr, _, _)| *addr);
+        Some(Self { segments, syms })
+    }
+
+    fn resolve(&self, file_offset: u64) -> Option<(&str, u64)> {
+        let addr = self
+            .segments
+            .iter()
+            .find(|(offset, _, size)| (*offset..offset + size).contains(&file_offset))
+            .map(|(offset, addr, _)| file_offset - offset + addr)?;
+        let (start, size, name) = match self.syms.partition_point(|(a, _, _)| *a <= addr) {
+            0 => return None,
+            i => &self.syms[i - 1],
+        };
+        // Symbols without a size are assumed to extend to the next symbol
+        match *size == 0 || addr < start + size {
+            true => Some((name.as_str(), addr - start)),
+            false => None,
+        }
+    }
+}
+
+// Lines like "7f2c1a000000-7f2c1a028000 r-xp 00028000 103:02 1234 /usr/lib/libc.so.6"
+fn read_maps(pid: u32) -> Vec<(std::ops::Range<u64>, u64, String)> {
+    let maps = std::fs::read_to_string(format!("/proc/{pid}/maps")).unwrap_or_default();
+    maps.lines()
+        .filter_map(|line| {
+            let mut cols = line.split_whitespace();
+            let (start, end) = cols.next()?.split_once('-')?;
+            let start = u64::from_str_radix(start, 16).ok()?;
+            let end = u64::from_str_radix(end, 16).ok()?;
+            let offset = u64::from_str_radix(cols.nth(1)?, 16).ok()?;
+            let path = cols.nth(2)?;
+            match path.starts_with('/') {
+                true => Some((start..end, offset, path.to_string())),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// Symbolizes stacks from a BPF program's `stack_traces` map. Kernel frames
+/// with /proc/kallsyms, user frames with the symbol tables of whatever the
+/// process had mapped at their addresses.
+pub(crate) struct Stacks {
+    map: libbpf_rs::MapHandle,
+    // Stacks which weren't stored, as their id was taken by another stack
+    collisions: libbpf_rs::MapHandle,
+    collisions_logged: u64,
+    ksyms: crate::ksyms::Ksyms,
+    // By their path in the process' mount namespace, None if unreadable
+    elfs: std::collections::HashMap<String, Option<ElfSyms>>,
+}
+
+impl Stacks {
+    /// None if the program doesn't take stacks.
+    pub fn from_object(object: &libbpf_rs::Object) -> Result<Option<Self>, crate::tool::Error> {
+        let Some(map) = object.map("stack_traces") else {
+            return Ok(None);
+        };
+        let map = libbpf_rs::MapHandle::try_clone(map).map_err(|_| crate::tool::Error::Libbpf)?;
+        let collisions = object
+            .map("stack_id_collisions")
+            .ok_or(crate::tool::Error::Libbpf)?;
+        let collisions =
+            libbpf_rs::MapHandle::try_clone(collisions).map_err(|_| crate::tool::Error::Libbpf)?;
+        Ok(Some(Self {
+            map,
+            collisions,
+            collisions_logged: 0,
+            ksyms: crate::ksyms::Ksyms::load(),
+            elfs: std::collections::HashMap::new(),
+        }))
+    }
+
+    fn addrs(&self, id: i32) -> Vec<u64> {
+        if id < 0 {
+            return Vec::new();
+        }
+        match self.map.lookup(&id.to_ne_bytes(), libbpf_rs::MapFlags::ANY) {
+            Ok(Some(data)) => data
+                .chunks_exact(8)
+                .map(|addr| u64::from_ne_bytes(addr.try_into().unwrap()))
+                .take_while(|addr| *addr != 0)
+                .collect(),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                log::error!("Error reading stack trace: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn kernel_frame(&self, addr: u64) -> Frame {
+        let sym = self.ksyms.resolve(addr);
+        Frame {
+            addr,
+            symbol: sym.map(|sym| sym.name.clone()),
+            offset: sym.map_or(0, |sym| addr - sym.addr),
+            object: None,
+        }
+    }
+
+    fn user_frame(
+        &mut self,
+        maps: &[(std::ops::Range<u64>, u64, String)],
+        pid: u32,
+        addr: u64,
+    ) -> Frame {
+        let mut frame = Frame {
+            addr,
+            symbol: None,
+            offset: 0,
+            object: None,
+        };
+        let Some((range, offset, path)) = maps.iter().find(|(range, _, _)| range.contains(&addr))
+        else {
+            return frame;
+        };
+        let elf = self
+            .elfs
+            .entry(path.clone())
+            .or_insert_with(|| ElfSyms::read(&format!("/proc/{pid}/root{path}")));
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        frame.object = Some(file_name.to_string());
+        if let Some((symbol, sym_offset)) = elf
+            .as_ref()
+            .and_then(|elf| elf.resolve(addr - range.start + offset))
+        {
+            frame.symbol = Some(symbol.to_string());
+            frame.offset = sym_offset;
+        }
+        frame
+    }
+
+    fn log_collisions(&mut self) {
+        let collisions = match self
+            .collisions
+            .lookup(&0u32.to_ne_bytes(), libbpf_rs::MapFlags::ANY)
+        {
+            Ok(Some(data)) => u64::from_ne_bytes(data[..8].try_into().unwrap()),
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Error reading stack id collisions: {:?}", e);
+                return;
+            }
+        };
+        if collisions > self.collisions_logged {
+            log::warn!(
+                "{} stacks weren't stored, their ids were taken by other stacks",
+                collisions - self.collisions_logged
+            );
+            self.collisions_logged = collisions;
+        }
+    }
+
+    /// For tools which read every stack they took, so that the map doesn't fill.
+    pub fn forget(&self, ids: StackIds) {
+        for id in [ids.kernel, ids.user].into_iter().filter(|id| *id >= 0) {
+            // Shared with an earlier sample, which was already forgotten
+            let _ = self.map.delete(&id.to_ne_bytes());
+        }
+    }
+
+    pub fn resolve(&mut self, ids: StackIds) -> Stack {
+        self.log_collisions();
+        let kernel = self
+            .addrs(ids.kernel)
+            .into_iter()
+            .map(|addr| self.kernel_frame(addr))
+            .collect();
+        let user_addrs = self.addrs(ids.user);
+        // The process may be gone by now, in which case the frames are just addresses
+        let maps = match user_addrs.is_empty() {
+            true => Vec::new(),
+            false => read_maps(ids.pid),
+        };
+        let user = user_addrs
+            .into_iter()
+            .map(|addr| self.user_frame(&maps, ids.pid, addr))
+            .collect();
+        Stack { kernel, user }
+    }
+}
@@ -33,3 +33,62 @@ macro_rules! impl_stream_for {
 }
 
 pub(crate) use impl_stream_for;
+
+// For tools which aggregate in BPF maps, rather than sending each event through
+// a perf or ring buffer. Every reporting interval, the maps are drained with the
+// tool's `collect(&mut self) -> Vec<Event<Value>>`. The tool needs `pending`,
+// `interval` and `next_report` fields to keep track of what's left to report.
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! impl_interval_stream_for {
+    ($Prog:ty, $Value:ty) => {
+        impl futures::Stream for $Prog {
+            type Item = $crate::event::Event<$Value>;
+            fn poll_next(
+                self: std::pin::Pin<&mut Self>,
+                ctx: &mut std::task::Context,
+            ) -> std::task::Poll<Option<Self::Item>> {
+                let this = self.get_mut();
+                let now = std::time::Instant::now();
+                if this.pending.is_empty() && now >= this.next_report {
+                    let collected = this.collect();
+                    this.pending.extend(collected);
+                    this.next_report = now + this.interval;
+                }
+                match this.pending.pop_front() {
+                    Some(ev) => std::task::Poll::Ready(Some(ev)),
+                    None => {
+                        let waker = ctx.waker().clone();
+                        let until_report = this.next_report.saturating_duration_since(now);
+                        tokio::spawn(async move {
+                            tokio::time::sleep(until_report).await;
+                            waker.wake();
+                        });
+                        std::task::Poll::Pending
+                    }
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use impl_interval_stream_for;
+
+// Reads and deletes every entry of an aggregating map, each in one step, so that
+// nothing is counted between reading and clearing. The keys are collected up
+// front, since deleting while iterating over them can skip some.
+pub(crate) fn drain(map: &libbpf_rs::Map) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let keys: Vec<Vec<u8>> = map.keys().collect();
+    let mut entries = Vec::with_capacity(keys.len());
+    for key in keys {
+        match map.lookup_and_delete(&key) {
+            Ok(Some(value)) => entries.push((key, value)),
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("Error draining {}: {:?}", map.name(), e);
+                break;
+            }
+        }
+    }
+    entries
+}
@@ -1,7 +1,40 @@
 mod skel {
     include!(concat!(env!("OUT_DIR"), "/skel_tcp_pkt_lat.rs"));
 }
-pub type Value = u64;
+
+const AF_INET: u16 = 2;
+
+/// A TCP packet, from when it was received to when its socket's receive
+/// buffer was next adjusted, i.e. when the packet was read. The addresses and
+/// ports are the receiving socket's (source) and its peer's (destination).
+#[derive(Debug, Clone, Copy)]
+pub struct TcpPkt {
+    pub lat_us: u64,
+    pub saddr: std::net::IpAddr,
+    pub daddr: std::net::IpAddr,
+    pub sport: u16,
+    pub dport: u16,
+}
+
+impl std::fmt::Display for TcpPkt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.lat_us)
+    }
+}
+
+// In network byte order, as the kernel keeps them, in the first word for ipv4
+fn ip_addr(family: u16, addr: [u32; 4]) -> std::net::IpAddr {
+    if family == AF_INET {
+        return std::net::Ipv4Addr::from(addr[0].to_ne_bytes()).into();
+    }
+    let mut octets = [0u8; 16];
+    for (word, chunk) in addr.iter().zip(octets.chunks_exact_mut(4)) {
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    std::net::Ipv6Addr::from(octets).into()
+}
+
+pub type Value = TcpPkt;
 pub struct TcpPktLat<'cls> {
     // Need to hold this to keep the attached probes alive
     _skel: skel::TcpPktLatSkel<'cls>,
@@ -9,6 +42,26 @@ pub struct TcpPktLat<'cls> {
     rx: std::sync::mpsc::Receiver<crate::event::Event<Value>>,
 }
 unsafe impl plain::Plain for skel::tcp_pkt_lat_types::event {}
-crate::event::impl_from_bytes_for!(TcpPktLat<'_>, Value, skel::tcp_pkt_lat_types::event);
+
+impl crate::event::FromBytes<Value> for TcpPktLat<'_> {
+    fn from_bytes(data: &[u8]) -> crate::event::Event<Value> {
+        let mut event = skel::tcp_pkt_lat_types::event::default();
+        plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
+        crate::event::Event {
+            time: crate::time::since_prog_start(event.ts),
+            task: event.task,
+            pid: event.pid as u32,
+            value: TcpPkt {
+                lat_us: event.lat_us,
+                saddr: ip_addr(event.family, event.saddr),
+                daddr: ip_addr(event.family, event.daddr),
+                sport: u16::from_be(event.sport),
+                dport: u16::from_be(event.dport),
+            },
+            stack: None,
+        }
+    }
+}
+
 crate::stream::impl_stream_for!(TcpPktLat<'_>, Value);
 crate::tool::impl_tool_for_ring_buf_bpf_prog!(TcpPktLat, skel::TcpPktLatSkelBuilder);
@@ -1,9 +1,52 @@
-static PROG_START: std::sync::Mutex<Option<std::time::Instant>> = std::sync::Mutex::new(None);
+struct Start {
+    instant: std::time::Instant,
+    ktime_ns: u64,
+}
+
+// Set once, and then read (by every event) without locking
+static PROG_START: std::sync::OnceLock<Start> = std::sync::OnceLock::new();
+
+fn start() -> &'static Start {
+    PROG_START.get_or_init(|| Start {
+        instant: std::time::Instant::now(),
+        ktime_ns: ktime_ns(),
+    })
+}
 
 pub fn prog_start() -> std::time::Instant {
-    let mut start = PROG_START.lock().unwrap();
-    if start.is_none() {
-        *start = Some(std::time::Instant::now());
-    }
-    start.unwrap()
+    start().instant
+}
+
+/// Now, by the kernel's monotonic clock (as with `bpf_ktime_get_ns()`), which
+/// events are timestamped by, in nanoseconds.
+pub fn ktime_ns() -> u64 {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// The time of an event, since this program's start, from its kernel timestamp.
+pub fn since_prog_start(ktime_ns: u64) -> std::time::Duration {
+    let start_ns = start().ktime_ns;
+    std::time::Duration::from_nanos(ktime_ns.saturating_sub(start_ns))
+}
+
+/// Now, since this program's start, on the same clock as events' times.
+pub fn now() -> std::time::Duration {
+    since_prog_start(ktime_ns())
+}
+
+/// By the system's clock, when the kernel's monotonic clock started, i.e. when
+/// the kernel booted (though later, by however long it's been suspended).
+/// Measured once, so that times converted with it are consistent.
+pub fn boot_time() -> std::time::SystemTime {
+    static BOOT_TIME: std::sync::OnceLock<std::time::SystemTime> = std::sync::OnceLock::new();
+    *BOOT_TIME
+        .get_or_init(|| std::time::SystemTime::now() - std::time::Duration::from_nanos(ktime_ns()))
+}
+
+/// By the system's clock, the time of an event (since this program's start).
+pub fn wall_clock(time: std::time::Duration) -> std::time::SystemTime {
+    let start_ns = start().ktime_ns;
+    boot_time() + std::time::Duration::from_nanos(start_ns) + time
 }
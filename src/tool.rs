@@ -24,9 +24,16 @@ pub trait Tool: futures::Stream {
         Self: Sized;
 }
 
+// Sets up the common configuration (min_lat_us, targ_pid, targ_tgid) for a BPF
+// program. Anything tool-specific can be set in the optional `configure` block,
+// which is given the open skeleton and the `Cfg`. Stacks are symbolized for
+// tools whose programs have a `stack_traces` map, if `targ_stacks` is set.
 #[allow(clippy::crate_in_macro_def)]
 macro_rules! impl_tool_for_perf_event_bpf_prog {
     ($Tool:ident, $Skel:ty) => {
+        crate::tool::impl_tool_for_perf_event_bpf_prog!($Tool, $Skel, |_open_skel, _cfg| {});
+    };
+    ($Tool:ident, $Skel:ty, |$open_skel:ident, $cfg:ident| $configure:block) => {
         impl crate::tool::Tool for $Tool<'_> {
             fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
                 use crate::event::FromBytes;
@@ -40,11 +47,24 @@ macro_rules! impl_tool_for_perf_event_bpf_prog {
                 open_skel.rodata_mut().min_lat_us = cfg.min_lat_us;
                 open_skel.rodata_mut().targ_pid = cfg.targ_pid;
                 open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+                {
+                    let $open_skel = &mut open_skel;
+                    let $cfg = &cfg;
+                    $configure
+                }
                 let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
                 skel.attach().map_err(|_| Error::Libbpf)?;
+                let mut stacks = match cfg.targ_stacks {
+                    true => crate::stack::Stacks::from_object(skel.object())?,
+                    false => None,
+                };
                 let ev_buf = libbpf_rs::PerfBufferBuilder::new(skel.maps().events())
                     .sample_cb(move |_cpu, data| {
-                        tx.send($Tool::from_bytes(data)).unwrap();
+                        let mut event = $Tool::from_bytes(data);
+                        if let (Some(stacks), Some(ids)) = (&mut stacks, $Tool::stack_ids(data)) {
+                            event.stack = Some(stacks.resolve(ids));
+                        }
+                        tx.send(event).unwrap();
                     })
                     .build()
                     .map_err(|_| Error::Libbpf)?;
@@ -61,6 +81,9 @@ macro_rules! impl_tool_for_perf_event_bpf_prog {
 #[allow(clippy::crate_in_macro_def)]
 macro_rules! impl_tool_for_ring_buf_bpf_prog {
     ($Tool:ident, $Skel:ty) => {
+        crate::tool::impl_tool_for_ring_buf_bpf_prog!($Tool, $Skel, |_open_skel, _cfg| {});
+    };
+    ($Tool:ident, $Skel:ty, |$open_skel:ident, $cfg:ident| $configure:block) => {
         impl crate::tool::Tool for $Tool<'_> {
             fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
                 use crate::event::FromBytes;
@@ -74,12 +97,25 @@ macro_rules! impl_tool_for_ring_buf_bpf_prog {
                 open_skel.rodata_mut().min_lat_us = cfg.min_lat_us;
                 open_skel.rodata_mut().targ_pid = cfg.targ_pid;
                 open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+                {
+                    let $open_skel = &mut open_skel;
+                    let $cfg = &cfg;
+                    $configure
+                }
                 let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
                 skel.attach().map_err(|_| Error::Libbpf)?;
+                let mut stacks = match cfg.targ_stacks {
+                    true => crate::stack::Stacks::from_object(skel.object())?,
+                    false => None,
+                };
                 let mut rb = libbpf_rs::RingBufferBuilder::new();
                 let maps = skel.maps();
                 rb.add(maps.events(), move |data| {
-                    tx.send($Tool::from_bytes(data)).unwrap();
+                    let mut event = $Tool::from_bytes(data);
+                    if let (Some(stacks), Some(ids)) = (&mut stacks, $Tool::stack_ids(data)) {
+                        event.stack = Some(stacks.resolve(ids));
+                    }
+                    tx.send(event).unwrap();
                     return 0;
                 })
                 .map_err(|_| Error::Libbpf)?;
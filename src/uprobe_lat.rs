@@ -0,0 +1,91 @@
+mod skel {
+    include!(concat!(env!("OUT_DIR"), "/skel_uprobe_lat.rs"));
+}
+
+/// A function in a binary or shared library, like "/usr/lib/libssl.so:SSL_read".
+/// Libraries given without a path are searched for in the usual places.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uprobe {
+    pub path: std::path::PathBuf,
+    pub symbol: String,
+}
+
+impl std::str::FromStr for Uprobe {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once(':') {
+            Some((path, symbol)) if !path.is_empty() && !symbol.is_empty() => Ok(Self {
+                path: path.into(),
+                symbol: symbol.to_string(),
+            }),
+            _ => Err(format!("Expected <path>:<symbol>, got '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Uprobe {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.symbol)
+    }
+}
+
+pub type Value = u64;
+pub struct UprobeLat<'cls> {
+    // Need to hold these to keep the attached probes alive
+    _skel: skel::UprobeLatSkel<'cls>,
+    _links: [libbpf_rs::Link; 2],
+    ev_buf: libbpf_rs::PerfBuffer<'cls>,
+    rx: std::sync::mpsc::Receiver<crate::event::Event<Value>>,
+}
+unsafe impl plain::Plain for skel::uprobe_lat_types::event {}
+crate::event::impl_from_bytes_for!(UprobeLat<'_>, Value, skel::uprobe_lat_types::event);
+crate::stream::impl_stream_for!(UprobeLat<'_>, Value);
+
+impl crate::tool::Tool for UprobeLat<'_> {
+    fn try_new(cfg: crate::cfg::Cfg) -> Result<Self, crate::tool::Error> {
+        use crate::event::FromBytes;
+        use crate::tool::Error;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::SkelBuilder;
+        let uprobe = cfg
+            .targ_uprobe
+            .ok_or(Error::Misconfig("A uprobe (<path>:<symbol>) is required"))?;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let skel_builder = skel::UprobeLatSkelBuilder::default();
+        let mut open_skel = skel_builder.open().map_err(|_| Error::Libbpf)?;
+        open_skel.rodata_mut().min_lat_us = cfg.min_lat_us;
+        open_skel.rodata_mut().targ_pid = cfg.targ_pid;
+        open_skel.rodata_mut().targ_tgid = cfg.targ_tgid;
+        let mut skel = open_skel.load().map_err(|_| Error::Libbpf)?;
+        // Only the target process is probed, if there is one
+        let pid = if cfg.targ_pid > 0 { cfg.targ_pid } else { -1 };
+        let attach = |prog: &mut libbpf_rs::Program, retprobe| {
+            let opts = libbpf_rs::UprobeOpts {
+                func_name: uprobe.symbol.clone(),
+                retprobe,
+                ..Default::default()
+            };
+            prog.attach_uprobe_with_opts(pid, &uprobe.path, 0, opts)
+                .map_err(|e| {
+                    log::error!("Failed to attach to {uprobe}: {e}");
+                    Error::Libbpf
+                })
+        };
+        let links = [
+            attach(skel.progs_mut().uprobe_entry(), false)?,
+            attach(skel.progs_mut().uprobe_exit(), true)?,
+        ];
+        let ev_buf = libbpf_rs::PerfBufferBuilder::new(skel.maps().events())
+            .sample_cb(move |_cpu, data| {
+                tx.send(UprobeLat::from_bytes(data)).unwrap();
+            })
+            .build()
+            .map_err(|_| Error::Libbpf)?;
+        Ok(Self {
+            _skel: skel,
+            _links: links,
+            ev_buf,
+            rx,
+        })
+    }
+}
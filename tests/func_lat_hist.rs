@@ -0,0 +1,35 @@
+// Needs root (or CAP_BPF and CAP_PERFMON), so it's run with `cargo test -- --ignored`
+
+use flaregun::tool::Tool;
+use futures::StreamExt;
+
+// getpid() calls this, and takes well under a microsecond
+const FUNC: &str = "__task_pid_nr_ns";
+
+#[tokio::test(flavor = "current_thread")]
+#[ignore = "needs root"]
+async fn counts_sub_microsecond_calls_in_the_first_slot() {
+    flaregun::must_bump_memlock_rlimit_once();
+    let cfg = flaregun::Cfg {
+        min_lat_us: 0,
+        targ_reporting_interval_ms: 100,
+        targ_pid: std::process::id() as i32,
+        targ_tgid: 0,
+        targ_dev: 0,
+        targ_filter_dev: false,
+        targ_filter_cgroup: false,
+        targ_filter_queued: false,
+        targ_bio_op: None,
+        targ_uprobe: None,
+        targ_func: Some(FUNC.to_string()),
+        targ_stacks: false,
+        targ_sample_freq_hz: 0,
+    };
+    let mut hists = flaregun::FuncLatHist::try_new(cfg).unwrap();
+    for _ in 0..1000 {
+        unsafe { libc::syscall(libc::SYS_getpid) };
+    }
+    let event = hists.next().await.unwrap();
+    assert_eq!(event.value.func, FUNC);
+    assert!(event.value.slots[0] > 0, "slots: {:?}", event.value.slots);
+}